@@ -0,0 +1,123 @@
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+
+use crate::{PostMeta, DATE_FORMAT};
+
+/// All posts of a year, available in the `archives` template variable
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ArchiveYear {
+    pub year: i32,
+    /// number of posts in this year
+    pub count: usize,
+    /// path of the archive page, relative to the output directory
+    pub rendered_to: String,
+    /// months with posts, newest first
+    pub months: Vec<ArchiveMonth>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ArchiveMonth {
+    pub year: i32,
+    pub month: u32,
+    /// first day of the month, for use with tera's `date` filter (e.g. to get the month name)
+    pub date: String,
+    /// number of posts in this month
+    pub count: usize,
+    /// path of the archive page, relative to the output directory
+    pub rendered_to: String,
+}
+
+fn post_date(meta: &PostMeta) -> Option<NaiveDate> {
+    let date = meta.header.as_ref()?.date.as_ref()?;
+    NaiveDate::parse_from_str(date, DATE_FORMAT).ok()
+}
+
+/// Group posts with a date by year and month, newest first. Posts without date are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::{PostHeader, PostMeta};
+/// use markhor::archive::collect_archives;
+///
+/// let post = |date: &str| PostMeta {
+///     source_file: format!("{date}.md"),
+///     rendered_to: format!("{date}.html"),
+///     header: Some(PostHeader {
+///         date: Some(date.to_string()),
+///         ..Default::default()
+///     }),
+///     preview_text: String::new(),
+/// };
+///
+/// let posts = [post("2022-03-05"), post("2022-03-01"), post("2022-01-10"), post("2021-12-24")];
+/// let posts: Vec<&PostMeta> = posts.iter().collect();
+///
+/// let archives = collect_archives(&posts);
+///
+/// assert_eq!(archives.len(), 2);
+/// assert_eq!(archives[0].year, 2022);
+/// assert_eq!(archives[0].count, 3);
+/// assert_eq!(archives[0].rendered_to, "2022/index.html");
+/// assert_eq!(archives[0].months.len(), 2);
+/// assert_eq!(archives[0].months[0].month, 3);
+/// assert_eq!(archives[0].months[0].count, 2);
+/// assert_eq!(archives[0].months[0].date, "2022-03-01");
+/// assert_eq!(archives[0].months[0].rendered_to, "2022/03/index.html");
+/// assert_eq!(archives[1].months[0].rendered_to, "2021/12/index.html");
+/// ```
+pub fn collect_archives(posts: &[&PostMeta]) -> Vec<ArchiveYear> {
+    let mut dates: Vec<NaiveDate> = posts.iter().filter_map(|meta| post_date(meta)).collect();
+    dates.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut archives: Vec<ArchiveYear> = Vec::new();
+
+    for date in dates {
+        let (year, month) = (date.year(), date.month());
+
+        if archives.last().map(|a| a.year) != Some(year) {
+            archives.push(ArchiveYear {
+                year,
+                count: 0,
+                rendered_to: format!("{year}/index.html"),
+                months: Vec::new(),
+            });
+        }
+        let archive_year = archives.last_mut().expect("year was just added");
+        archive_year.count += 1;
+
+        if archive_year.months.last().map(|m| m.month) != Some(month) {
+            archive_year.months.push(ArchiveMonth {
+                year,
+                month,
+                date: format!("{year:04}-{month:02}-01"),
+                count: 0,
+                rendered_to: format!("{year}/{month:02}/index.html"),
+            });
+        }
+        archive_year
+            .months
+            .last_mut()
+            .expect("month was just added")
+            .count += 1;
+    }
+
+    archives
+}
+
+/// Posts from `year` and, if given, `month`, keeping the order of `posts`
+pub fn posts_in_archive<'a>(
+    posts: &[&'a PostMeta],
+    year: i32,
+    month: Option<u32>,
+) -> Vec<&'a PostMeta> {
+    posts
+        .iter()
+        .filter(|meta| {
+            post_date(meta).is_some_and(|date| {
+                date.year() == year && month.is_none_or(|month| date.month() == month)
+            })
+        })
+        .copied()
+        .collect()
+}
@@ -0,0 +1,245 @@
+use std::ops::{Range, RangeInclusive};
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+use crate::config::MarkdownOptions;
+use crate::escape_xml;
+use crate::highlight::{highlight_lines, pre_tag};
+
+/// Byte ranges of all code in `markdown`: fenced and indented code blocks and inline code spans.
+///
+/// Shortcodes and wiki links in these ranges are kept as they are, so they can be documented in posts.
+///
+/// # Examples
+///
+/// ````
+/// use markhor::codeblock::code_ranges;
+///
+/// let markdown = "a `b` c\n\n```rust\ncode\n```\n\n    indented\n\n~~~\nopen";
+///
+/// let ranges: Vec<&str> = code_ranges(markdown).into_iter().map(|range| &markdown[range]).collect();
+/// assert_eq!(ranges, vec!["`b`", "```rust\ncode\n```", "indented\n", "~~~\nopen"]);
+/// ````
+pub fn code_ranges(markdown: &str) -> Vec<Range<usize>> {
+    Parser::new(markdown)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Options of a fenced code block, parsed from its info string,
+/// e.g. ```` ```rust,linenos,hl_lines=3-5,title=src/main.rs ````
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct CodeBlockOptions {
+    pub language: Option<String>,
+    /// `linenos`, number every line
+    pub line_numbers: bool,
+    /// `hl_lines=1 3-5`, lines to highlight, starting at 1
+    pub highlight_lines: Vec<RangeInclusive<usize>>,
+    /// `title=...`, caption of the code block, e.g. a file name
+    pub title: Option<String>,
+}
+
+impl CodeBlockOptions {
+    /// Parse a comma separated info string. The language is the first value, unless it is an option.
+    ///
+    /// Values containing commas can be put in double quotes, e.g. `title="a, b.rs"`.
+    /// Unknown options and invalid line ranges are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use markhor::codeblock::CodeBlockOptions;
+    ///
+    /// let options = CodeBlockOptions::parse("rust,linenos,hl_lines=1 3-5,title=src/main.rs");
+    ///
+    /// assert_eq!(options.language.as_deref(), Some("rust"));
+    /// assert!(options.line_numbers);
+    /// assert_eq!(options.highlight_lines, vec![1..=1, 3..=5]);
+    /// assert_eq!(options.title.as_deref(), Some("src/main.rs"));
+    ///
+    /// let quoted = CodeBlockOptions::parse(r#"rust,title="a,b.rs",linenos"#);
+    /// assert_eq!(quoted.title.as_deref(), Some("a,b.rs"));
+    /// assert!(quoted.line_numbers);
+    ///
+    /// assert_eq!(CodeBlockOptions::parse("linenos").language, None);
+    /// assert_eq!(CodeBlockOptions::parse("rust ignore").language.as_deref(), Some("rust"));
+    /// assert_eq!(CodeBlockOptions::parse(""), CodeBlockOptions::default());
+    /// ```
+    pub fn parse(info: &str) -> Self {
+        let mut options = CodeBlockOptions::default();
+
+        for (index, value) in split_info(info).into_iter().map(str::trim).enumerate() {
+            match value.split_once('=') {
+                Some(("hl_lines", ranges)) => {
+                    options
+                        .highlight_lines
+                        .extend(ranges.split_whitespace().filter_map(parse_range));
+                }
+                Some(("title", title)) => {
+                    let title = title.trim();
+                    let unquoted = title
+                        .strip_prefix('"')
+                        .and_then(|title| title.strip_suffix('"'));
+                    options.title = Some(unquoted.unwrap_or(title).to_string());
+                }
+                Some(_) => {}
+                None if value == "linenos" => options.line_numbers = true,
+                None if index == 0 => {
+                    options.language = value.split_whitespace().next().map(str::to_string)
+                }
+                None => {}
+            }
+        }
+
+        options
+    }
+
+    /// Whether any option besides the language is set
+    fn has_line_options(&self) -> bool {
+        self.line_numbers || !self.highlight_lines.is_empty() || self.title.is_some()
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.highlight_lines
+            .iter()
+            .any(|range| range.contains(&line))
+    }
+}
+
+/// split `info` at commas that are not inside double quotes
+fn split_info(info: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+
+    for (index, c) in info.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                values.push(&info[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(&info[start..]);
+
+    values
+}
+
+/// parse `3` or `3-5`
+fn parse_range(range: &str) -> Option<RangeInclusive<usize>> {
+    match range.split_once('-') {
+        Some((start, end)) => Some(start.parse().ok()?..=end.parse().ok()?),
+        None => {
+            let line = range.parse().ok()?;
+            Some(line..=line)
+        }
+    }
+}
+
+/// Render a fenced code block, syntax highlighted if enabled in `markdown_options` and the language is known.
+///
+/// With line options, every line is wrapped in `<span class="line">`, highlighted lines get the
+/// additional class `hl`, line numbers are prepended as `<span class="line-number">`.
+/// A title wraps the block in `<figure class="code-block">` with a `<figcaption>`.
+///
+/// Returns `None` if there is nothing to change, so the code block can be rendered as usual.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::codeblock::{render_code_block, CodeBlockOptions};
+/// use markhor::config::MarkdownOptions;
+///
+/// let options = CodeBlockOptions::parse("text,linenos,hl_lines=2,title=notes.txt");
+/// let html = render_code_block("a\nb < c\n", &options, &MarkdownOptions::default()).unwrap();
+///
+/// assert_eq!(
+///     html,
+///     r#"<figure class="code-block"><figcaption>notes.txt</figcaption><pre><code class="language-text"><span class="line"><span class="line-number">1</span>a</span>
+/// <span class="line hl"><span class="line-number">2</span>b &lt; c</span>
+/// </code></pre></figure>"#
+/// );
+///
+/// let plain = CodeBlockOptions::parse("rust");
+/// assert_eq!(render_code_block("code", &plain, &MarkdownOptions::default()), None);
+/// ```
+pub fn render_code_block(
+    code: &str,
+    options: &CodeBlockOptions,
+    markdown_options: &MarkdownOptions,
+) -> Option<String> {
+    let language = options.language.as_deref();
+
+    let highlighted = language
+        .filter(|_| markdown_options.highlight)
+        .and_then(|language| {
+            let lines = highlight_lines(
+                code,
+                language,
+                markdown_options.highlight_style,
+                &markdown_options.highlight_theme,
+            )?;
+            let pre = pre_tag(
+                markdown_options.highlight_style,
+                &markdown_options.highlight_theme,
+            )?;
+            Some((pre, lines))
+        });
+
+    if highlighted.is_none() && !options.has_line_options() {
+        return None;
+    }
+
+    let (pre, lines) = highlighted
+        .unwrap_or_else(|| ("<pre>".to_string(), code.lines().map(escape_xml).collect()));
+
+    let mut html = String::new();
+    if let Some(title) = &options.title {
+        html.push_str(&format!(
+            r#"<figure class="code-block"><figcaption>{}</figcaption>"#,
+            escape_xml(title)
+        ));
+    }
+
+    html.push_str(&pre);
+    match language {
+        Some(language) => html.push_str(&format!(
+            r#"<code class="language-{}">"#,
+            escape_xml(language)
+        )),
+        None => html.push_str("<code>"),
+    }
+
+    for (index, line) in lines.iter().enumerate() {
+        let number = index + 1;
+        if options.has_line_options() {
+            let class = if options.is_highlighted(number) {
+                "line hl"
+            } else {
+                "line"
+            };
+            html.push_str(&format!(r#"<span class="{class}">"#));
+            if options.line_numbers {
+                html.push_str(&format!(r#"<span class="line-number">{number}</span>"#));
+            }
+            html.push_str(line);
+            html.push_str("</span>\n");
+        } else {
+            html.push_str(line);
+            html.push('\n');
+        }
+    }
+
+    html.push_str("</code></pre>");
+    if options.title.is_some() {
+        html.push_str("</figure>");
+    }
+
+    Some(html)
+}
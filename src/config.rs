@@ -0,0 +1,263 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::highlight::HighlightStyle;
+
+/// name of the optional configuration file located at the site root
+pub const CONFIG_FILE: &str = "markhor.toml";
+
+/// Site configuration, read from [CONFIG_FILE].
+///
+/// Every value is optional, a missing file or key falls back to the defaults.
+/// The top-level values and [Config::extra] are available as `site` in templates,
+/// the tables [Config::directories], [Config::feeds], [Config::sitemap], [Config::markdown] and [Config::search] are not.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub title: Option<String>,
+    pub base_url: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    /// keep the subdirectory of a post inside the posts directory in its output path
+    pub mirror_directories: bool,
+    /// pattern for post output paths, e.g. `/{year}/{month}/{slug}/`,
+    /// see [`crate::permalink::expand_permalink`]
+    pub permalink: Option<String>,
+    /// number of posts per index and category page, all posts on one page if not set
+    pub paginate_by: Option<usize>,
+    /// number of related posts per post, not computed if not set,
+    /// see [`crate::related::RelatedPosts`]
+    pub related_posts: Option<usize>,
+    /// write every post as JSON next to its html file, see [`crate::export::render_json_export`]
+    pub json_export: bool,
+    /// source and output directories, relative to the site root
+    #[serde(skip_serializing)]
+    pub directories: Directories,
+    /// Atom and RSS feed settings
+    #[serde(skip_serializing)]
+    pub feeds: Feeds,
+    #[serde(skip_serializing)]
+    pub sitemap: Sitemap,
+    /// markdown conversion settings
+    #[serde(skip_serializing)]
+    pub markdown: MarkdownOptions,
+    /// client-side search index, see [`crate::search::render_search_index`]
+    #[serde(skip_serializing)]
+    pub search: Search,
+    /// arbitrary values for use in templates
+    pub extra: toml::value::Table,
+    /// the directory containing the site sources
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct Directories {
+    pub posts: PathBuf,
+    pub pages: PathBuf,
+    pub output: PathBuf,
+    #[serde(rename = "static")]
+    pub static_files: PathBuf,
+    pub templates: PathBuf,
+}
+
+impl Default for Directories {
+    fn default() -> Self {
+        Directories {
+            posts: PathBuf::from("posts"),
+            pages: PathBuf::from("pages"),
+            output: PathBuf::from("out"),
+            static_files: PathBuf::from("static"),
+            templates: PathBuf::from("templates"),
+        }
+    }
+}
+
+/// Feeds are only generated if [Config::base_url] is set, as they require absolute URLs
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct Feeds {
+    pub enabled: bool,
+    /// maximum number of posts per feed
+    pub limit: usize,
+    /// include the full post content instead of the preview text
+    pub full_content: bool,
+    /// generate an additional feed for every category
+    pub categories: bool,
+}
+
+impl Default for Feeds {
+    fn default() -> Self {
+        Feeds {
+            enabled: true,
+            limit: 20,
+            full_content: false,
+            categories: true,
+        }
+    }
+}
+
+/// The sitemap is only generated if [Config::base_url] is set, as it requires absolute URLs
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct Sitemap {
+    pub enabled: bool,
+    /// generate a `robots.txt` pointing at the sitemap
+    pub robots_txt: bool,
+}
+
+impl Default for Sitemap {
+    fn default() -> Self {
+        Sitemap {
+            enabled: true,
+            robots_txt: true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct MarkdownOptions {
+    /// add a self-link anchor to every heading
+    pub heading_anchors: bool,
+    /// highlight fenced code blocks at build time, see [`crate::highlight`]
+    pub highlight: bool,
+    /// one of the themes bundled with syntect
+    pub highlight_theme: String,
+    pub highlight_style: HighlightStyle,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        MarkdownOptions {
+            heading_anchors: false,
+            highlight: false,
+            highlight_theme: "base16-ocean.dark".to_string(),
+            highlight_style: HighlightStyle::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+#[serde(default)]
+pub struct Search {
+    pub enabled: bool,
+    /// write an inverted index instead of a token list per post
+    pub inverted_index: bool,
+}
+
+impl Config {
+    /// Read [CONFIG_FILE] from `root`, or use the default configuration if there is none
+    pub fn load(root: impl AsRef<Path>) -> Result<Config, Box<dyn Error>> {
+        let root = root.as_ref();
+        let config_file = root.join(CONFIG_FILE);
+
+        let mut config = if config_file.is_file() {
+            info!("Using configuration from {}", config_file.display());
+            let source = fs::read_to_string(&config_file)?;
+            toml::from_str(&source)?
+        } else {
+            info!("No {} found, using defaults", CONFIG_FILE);
+            Config::default()
+        };
+        // tera fails to find templates using a glob starting with `./`
+        config.root = root
+            .components()
+            .filter(|component| component != &Component::CurDir)
+            .collect();
+
+        Ok(config)
+    }
+
+    pub fn posts_dir(&self) -> PathBuf {
+        self.root.join(&self.directories.posts)
+    }
+
+    pub fn pages_dir(&self) -> PathBuf {
+        self.root.join(&self.directories.pages)
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        self.root.join(&self.directories.output)
+    }
+
+    pub fn static_dir(&self) -> PathBuf {
+        self.root.join(&self.directories.static_files)
+    }
+
+    pub fn templates_dir(&self) -> PathBuf {
+        self.root.join(&self.directories.templates)
+    }
+
+    /// Prefix `path` (relative to the output directory) with [Config::base_url]
+    ///
+    /// Returns `None` if there is no base URL
+    pub fn absolute_url(&self, path: &str) -> Option<String> {
+        self.base_url.as_ref().map(|base_url| {
+            format!(
+                "{}/{}",
+                base_url.trim_end_matches('/'),
+                path.trim_start_matches('/')
+            )
+        })
+    }
+
+    /// glob matching every file in [Config::templates_dir], as expected by [tera::Tera::new]
+    pub fn templates_glob(&self) -> String {
+        format!("{}/**/*", self.templates_dir().display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Config, Directories};
+
+    #[test]
+    fn test_config_defaults() {
+        let config: Config = toml::from_str("").unwrap();
+
+        assert_eq!(config, Config::default());
+        assert_eq!(config.directories, Directories::default());
+        assert_eq!(config.templates_glob(), "templates/**/*");
+    }
+
+    #[test]
+    fn test_config_should_read_values() {
+        let input = r#"
+title = "My site"
+base_url = "https://example.com"
+
+[directories]
+posts = "content"
+output = "public"
+
+[extra]
+github = "tufteddeer"
+"#;
+        let mut config: Config = toml::from_str(input).unwrap();
+        config.root = PathBuf::from("site");
+
+        assert_eq!(config.title.as_deref(), Some("My site"));
+        assert_eq!(config.base_url.as_deref(), Some("https://example.com"));
+        assert_eq!(config.author, None);
+        assert_eq!(config.extra["github"].as_str(), Some("tufteddeer"));
+
+        assert_eq!(config.posts_dir(), PathBuf::from("site/content"));
+        assert_eq!(config.output_dir(), PathBuf::from("site/public"));
+        assert_eq!(config.static_dir(), PathBuf::from("site/static"));
+        assert_eq!(config.templates_glob(), "site/templates/**/*");
+
+        assert_eq!(
+            config.absolute_url("notes/post.html").as_deref(),
+            Some("https://example.com/notes/post.html")
+        );
+        assert_eq!(Config::default().absolute_url("index.html"), None);
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use crate::{OutputFile, Post, PostMeta};
+
+/// file containing the metadata of all posts, relative to the output directory
+pub const POSTS_INDEX: &str = "posts.json";
+
+/// Serialize every [Post] (metadata, html content and headings) into a JSON file next to its html file,
+/// plus an index of all post metadata in [POSTS_INDEX].
+///
+/// `posts` are expected to be sorted newest first, the order is kept in the index.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::{Post, PostMeta};
+/// use markhor::export::render_json_export;
+///
+/// let post = Post {
+///     meta: PostMeta {
+///         source_file: "post.md".to_string(),
+///         rendered_to: "notes/post.html".to_string(),
+///         header: None,
+///         preview_text: "preview".to_string(),
+///     },
+///     content: "<p>preview</p>".to_string(),
+///     headings: Vec::new(),
+///     links: Vec::new(),
+/// };
+///
+/// let files = render_json_export(&[&post]).unwrap();
+///
+/// assert_eq!(files[0].path, "notes/post.json");
+/// assert!(files[0].content.contains(r#""content": "<p>preview</p>""#));
+/// assert_eq!(files[1].path, "posts.json");
+/// ```
+pub fn render_json_export(posts: &[&Post]) -> Result<Vec<OutputFile>, serde_json::Error> {
+    let mut files = Vec::with_capacity(posts.len() + 1);
+
+    for post in posts {
+        let path = Path::new(&post.meta.rendered_to).with_extension("json");
+
+        files.push(OutputFile {
+            path: path.to_string_lossy().to_string(),
+            content: serde_json::to_string_pretty(post)?,
+        });
+    }
+
+    let index: Vec<&PostMeta> = posts.iter().map(|post| &post.meta).collect();
+    files.push(OutputFile {
+        path: POSTS_INDEX.to_string(),
+        content: serde_json::to_string_pretty(&index)?,
+    });
+
+    Ok(files)
+}
@@ -0,0 +1,428 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::{escape_xml, OutputFile, Post, DATE_FORMAT};
+
+/// file name of Atom feeds
+pub const ATOM_FEED: &str = "feed.xml";
+/// file name of RSS feeds
+pub const RSS_FEED: &str = "rss.xml";
+/// file name of JSON feeds
+pub const JSON_FEED: &str = "feed.json";
+
+/// Generate an Atom, an RSS and a JSON feed for `posts`, which are expected to be sorted newest first.
+///
+/// The feeds are placed in `dir` (relative to the output directory, empty for the site root).
+/// Returns no files if there is no [Config::base_url].
+pub fn render_feeds(
+    config: &Config,
+    title: &str,
+    dir: &str,
+    posts: &[&Post],
+) -> Result<Vec<OutputFile>, serde_json::Error> {
+    let feed_config = &config.feeds;
+    if !feed_config.enabled || config.base_url.is_none() {
+        return Ok(Vec::new());
+    }
+
+    let posts: Vec<FeedEntry> = posts
+        .iter()
+        .take(feed_config.limit)
+        .map(|post| FeedEntry::new(config, post))
+        .collect();
+
+    let atom_path = feed_path(dir, ATOM_FEED);
+    let rss_path = feed_path(dir, RSS_FEED);
+    let json_path = feed_path(dir, JSON_FEED);
+
+    Ok(vec![
+        OutputFile {
+            content: atom_feed(config, title, &atom_path, &posts),
+            path: atom_path,
+        },
+        OutputFile {
+            content: rss_feed(config, title, &rss_path, &posts),
+            path: rss_path,
+        },
+        OutputFile {
+            content: json_feed(config, title, &json_path, &posts)?,
+            path: json_path,
+        },
+    ])
+}
+
+fn feed_path(dir: &str, file: &str) -> String {
+    if dir.is_empty() {
+        file.to_string()
+    } else {
+        format!("{dir}/{file}")
+    }
+}
+
+/// values of a single post, as needed by both feed formats
+struct FeedEntry {
+    title: String,
+    url: String,
+    date: Option<NaiveDateTime>,
+    /// `updated` header value, or the date if there is none
+    updated: Option<NaiveDateTime>,
+    category: Option<String>,
+    tags: Vec<String>,
+    content: String,
+}
+
+impl FeedEntry {
+    fn new(config: &Config, post: &Post) -> Self {
+        let meta = &post.meta;
+        let header = meta.header.as_ref();
+
+        let date = header.and_then(|h| parse_date(h.date.as_ref()));
+
+        FeedEntry {
+            title: header
+                .and_then(|h| h.title.clone())
+                .unwrap_or_else(|| meta.source_file.clone()),
+            url: config.absolute_url(&meta.rendered_to).unwrap_or_default(),
+            date,
+            updated: header.and_then(|h| parse_date(h.updated.as_ref())).or(date),
+            category: header.and_then(|h| h.category.clone()),
+            tags: header.and_then(|h| h.tags.clone()).unwrap_or_default(),
+            content: if config.feeds.full_content {
+                post.content.clone()
+            } else {
+                meta.preview_text.clone()
+            },
+        }
+    }
+}
+
+fn parse_date(date: Option<&String>) -> Option<NaiveDateTime> {
+    date.and_then(|date| NaiveDate::parse_from_str(date, DATE_FORMAT).ok())
+        .map(|date| date.and_time(NaiveTime::MIN))
+}
+
+/// newest update of all entries, or the Unix epoch if none has a date.
+///
+/// Undated entries use this date as well, it does not depend on the time of the build,
+/// so rebuilding an unchanged site results in the same feeds.
+fn last_updated(entries: &[FeedEntry]) -> NaiveDateTime {
+    entries
+        .iter()
+        .filter_map(|entry| entry.updated)
+        .max()
+        .unwrap_or_else(|| {
+            NaiveDate::from_ymd_opt(1970, 1, 1)
+                .expect("the Unix epoch is a valid date")
+                .and_time(NaiveTime::MIN)
+        })
+}
+
+fn atom_date(date: &NaiveDateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn rss_date(date: &NaiveDateTime) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S +0000").to_string()
+}
+
+fn atom_feed(config: &Config, title: &str, path: &str, entries: &[FeedEntry]) -> String {
+    let updated = last_updated(entries);
+    let site_url = config.absolute_url("").unwrap_or_default();
+    let feed_url = config.absolute_url(path).unwrap_or_default();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape_xml(title)));
+    if let Some(description) = &config.description {
+        xml.push_str(&format!(
+            "  <subtitle>{}</subtitle>\n",
+            escape_xml(description)
+        ));
+    }
+    xml.push_str(&format!("  <id>{}</id>\n", escape_xml(&feed_url)));
+    xml.push_str(&format!(
+        "  <link href=\"{}\" rel=\"self\"/>\n",
+        escape_xml(&feed_url)
+    ));
+    xml.push_str(&format!("  <link href=\"{}\"/>\n", escape_xml(&site_url)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", atom_date(&updated)));
+    // Atom requires an author, fall back to the site title
+    let author = config
+        .author
+        .as_deref()
+        .or(config.title.as_deref())
+        .unwrap_or(title);
+    xml.push_str(&format!(
+        "  <author><name>{}</name></author>\n",
+        escape_xml(author)
+    ));
+
+    let content_tag = if config.feeds.full_content {
+        "content"
+    } else {
+        "summary"
+    };
+
+    for entry in entries {
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_xml(&entry.title)
+        ));
+        xml.push_str(&format!("    <id>{}</id>\n", escape_xml(&entry.url)));
+        xml.push_str(&format!(
+            "    <link href=\"{}\"/>\n",
+            escape_xml(&entry.url)
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            atom_date(&entry.updated.unwrap_or(updated))
+        ));
+        if let Some(category) = &entry.category {
+            xml.push_str(&format!(
+                "    <category term=\"{}\"/>\n",
+                escape_xml(category)
+            ));
+        }
+        xml.push_str(&format!(
+            "    <{content_tag} type=\"html\">{}</{content_tag}>\n",
+            escape_xml(&entry.content)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn rss_feed(config: &Config, title: &str, path: &str, entries: &[FeedEntry]) -> String {
+    let site_url = config.absolute_url("").unwrap_or_default();
+    let feed_url = config.absolute_url(path).unwrap_or_default();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("  <channel>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", escape_xml(title)));
+    xml.push_str(&format!("    <link>{}</link>\n", escape_xml(&site_url)));
+    xml.push_str(&format!(
+        "    <description>{}</description>\n",
+        escape_xml(config.description.as_deref().unwrap_or(title))
+    ));
+    xml.push_str(&format!(
+        "    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape_xml(&feed_url)
+    ));
+    xml.push_str(&format!(
+        "    <lastBuildDate>{}</lastBuildDate>\n",
+        rss_date(&last_updated(entries))
+    ));
+
+    for entry in entries {
+        xml.push_str("    <item>\n");
+        xml.push_str(&format!(
+            "      <title>{}</title>\n",
+            escape_xml(&entry.title)
+        ));
+        xml.push_str(&format!("      <link>{}</link>\n", escape_xml(&entry.url)));
+        xml.push_str(&format!("      <guid>{}</guid>\n", escape_xml(&entry.url)));
+        if let Some(date) = &entry.date {
+            xml.push_str(&format!("      <pubDate>{}</pubDate>\n", rss_date(date)));
+        }
+        if let Some(category) = &entry.category {
+            xml.push_str(&format!(
+                "      <category>{}</category>\n",
+                escape_xml(category)
+            ));
+        }
+        xml.push_str(&format!(
+            "      <description>{}</description>\n",
+            escape_xml(&entry.content)
+        ));
+        xml.push_str("    </item>\n");
+    }
+
+    xml.push_str("  </channel>\n");
+    xml.push_str("</rss>\n");
+    xml
+}
+
+/// [JSON Feed 1.1](https://www.jsonfeed.org/version/1.1/)
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor<'a>>,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<&'a str>,
+}
+
+fn json_feed(
+    config: &Config,
+    title: &str,
+    path: &str,
+    entries: &[FeedEntry],
+) -> Result<String, serde_json::Error> {
+    let full_content = config.feeds.full_content;
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title,
+        home_page_url: config.absolute_url("").unwrap_or_default(),
+        feed_url: config.absolute_url(path).unwrap_or_default(),
+        description: config.description.as_deref(),
+        authors: config
+            .author
+            .iter()
+            .map(|name| JsonFeedAuthor { name })
+            .collect(),
+        items: entries
+            .iter()
+            .map(|entry| JsonFeedItem {
+                id: &entry.url,
+                url: &entry.url,
+                title: &entry.title,
+                content_html: full_content.then_some(entry.content.as_str()),
+                content_text: (!full_content).then_some(entry.content.as_str()),
+                date_published: entry.date.as_ref().map(atom_date),
+                tags: entry.tags.iter().map(String::as_str).collect(),
+            })
+            .collect(),
+    };
+
+    serde_json::to_string_pretty(&feed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_feeds;
+    use crate::config::Config;
+    use crate::{Post, PostHeader, PostMeta};
+
+    fn post(name: &str, date: &str) -> Post {
+        Post {
+            meta: PostMeta {
+                source_file: format!("{name}.md"),
+                rendered_to: format!("notes/{name}.html"),
+                header: Some(PostHeader {
+                    title: Some(format!("{name} & more")),
+                    date: Some(date.to_string()),
+                    category: Some("notes".to_string()),
+                    ..Default::default()
+                }),
+                preview_text: "preview".to_string(),
+            },
+            content: "<p>full</p>".to_string(),
+            headings: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_feeds() {
+        let mut config = Config {
+            base_url: Some("https://example.com/".to_string()),
+            ..Default::default()
+        };
+        config.feeds.limit = 1;
+
+        let newer = post("newer", "2022-03-05");
+        let older = post("older", "2022-01-01");
+        let posts = vec![&newer, &older];
+
+        let files = render_feeds(&config, "My site", "notes", &posts).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].path, "notes/feed.xml");
+        assert_eq!(files[1].path, "notes/rss.xml");
+        assert_eq!(files[2].path, "notes/feed.json");
+
+        let atom = &files[0].content;
+        assert!(atom.contains("<id>https://example.com/notes/feed.xml</id>"));
+        assert!(atom.contains("<updated>2022-03-05T00:00:00Z</updated>"));
+        assert!(atom.contains("<title>newer &amp; more</title>"));
+        assert!(atom.contains("<link href=\"https://example.com/notes/newer.html\"/>"));
+        assert!(atom.contains("<summary type=\"html\">preview</summary>"));
+        assert!(!atom.contains("older"));
+
+        let rss = &files[1].content;
+        assert!(rss.contains("<pubDate>Sat, 05 Mar 2022 00:00:00 +0000</pubDate>"));
+        assert!(rss.contains("<description>preview</description>"));
+
+        let json: serde_json::Value = serde_json::from_str(&files[2].content).unwrap();
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["feed_url"], "https://example.com/notes/feed.json");
+        assert_eq!(json["items"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            json["items"][0]["id"],
+            "https://example.com/notes/newer.html"
+        );
+        assert_eq!(json["items"][0]["date_published"], "2022-03-05T00:00:00Z");
+        assert_eq!(json["items"][0]["content_text"], "preview");
+
+        config.feeds.full_content = true;
+        let files = render_feeds(&config, "My site", "", &posts).unwrap();
+        assert_eq!(files[0].path, "feed.xml");
+        assert!(files[0]
+            .content
+            .contains("<content type=\"html\">&lt;p&gt;full&lt;/p&gt;</content>"));
+
+        assert!(files[0]
+            .content
+            .contains("<author><name>My site</name></author>"));
+
+        config.author = Some("Jane Doe".to_string());
+        let mut undated = post("undated", "");
+        undated.meta.header.as_mut().unwrap().date = None;
+        let files = render_feeds(&config, "My site", "", &[&undated]).unwrap();
+        let atom = &files[0].content;
+        assert!(atom.contains("<author><name>Jane Doe</name></author>"));
+        assert!(atom.contains("<updated>1970-01-01T00:00:00Z</updated>"));
+        assert_eq!(
+            files[0].content,
+            render_feeds(&config, "My site", "", &[&undated]).unwrap()[0].content
+        );
+        config.feeds.limit = 2;
+
+        let mut updated = post("updated", "2022-03-05");
+        updated.meta.header.as_mut().unwrap().updated = Some("2022-04-01".to_string());
+        let files = render_feeds(&config, "My site", "", &[&updated, &older]).unwrap();
+        let atom = &files[0].content;
+        assert!(atom.contains("  <updated>2022-04-01T00:00:00Z</updated>\n"));
+        assert!(atom.contains("    <updated>2022-04-01T00:00:00Z</updated>\n"));
+        assert!(atom.contains("    <updated>2022-01-01T00:00:00Z</updated>\n"));
+        assert!(files[1]
+            .content
+            .contains("<pubDate>Sat, 05 Mar 2022 00:00:00 +0000</pubDate>"));
+
+        config.base_url = None;
+        assert!(render_feeds(&config, "My site", "", &posts)
+            .unwrap()
+            .is_empty());
+    }
+}
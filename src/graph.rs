@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::links::normalize;
+use crate::{OutputFile, Post, PostMeta};
+
+/// file containing the link graph, relative to the output directory
+pub const GRAPH_FILE: &str = "graph.json";
+
+/// A post in the link graph
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct GraphNode<'a> {
+    /// source file of the post, used to reference it in [GraphEdge]s
+    pub id: String,
+    pub title: Option<&'a str>,
+    /// absolute path of the post, e.g. `/notes/post.html`
+    pub url: String,
+}
+
+/// A link from one post to another, both given by their [GraphNode::id]
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+}
+
+/// Links between posts, see [`Post::links`]
+///
+/// # Examples
+///
+/// ```
+/// use markhor::{Post, PostMeta};
+/// use markhor::graph::LinkGraph;
+///
+/// let post = |source: &str, links: &[&str]| Post {
+///     meta: PostMeta {
+///         source_file: source.to_string(),
+///         rendered_to: source.replace(".md", ".html"),
+///         ..Default::default()
+///     },
+///     links: links.iter().map(|link| link.to_string()).collect(),
+///     ..Default::default()
+/// };
+/// let garden = post("garden.md", &[]);
+/// let seeds = post("seeds.md", &["garden.md", "draft.md"]);
+/// let soil = post("soil.md", &["garden.md", "seeds.md", "soil.md"]);
+///
+/// let graph = LinkGraph::new(&[&soil, &seeds, &garden]);
+/// assert_eq!(graph.backlinks(&garden.meta), vec![&soil.meta, &seeds.meta]);
+/// assert_eq!(graph.backlinks(&seeds.meta), vec![&soil.meta]);
+/// assert!(graph.backlinks(&soil.meta).is_empty());
+///
+/// let json = graph.render_graph().unwrap();
+/// assert_eq!(json.path, "graph.json");
+/// assert_eq!(
+///     json.content,
+///     r#"{"nodes":[{"id":"soil.md","title":null,"url":"/soil.html"},{"id":"seeds.md","title":null,"url":"/seeds.html"},{"id":"garden.md","title":null,"url":"/garden.html"}],"edges":[{"source":"soil.md","target":"garden.md"},{"source":"soil.md","target":"seeds.md"},{"source":"seeds.md","target":"garden.md"}]}"#
+/// );
+/// ```
+pub struct LinkGraph<'a> {
+    posts: Vec<&'a Post>,
+    /// posts linking to a post, keyed by its normalized source file
+    backlinks: HashMap<String, Vec<&'a PostMeta>>,
+}
+
+impl<'a> LinkGraph<'a> {
+    /// Collect the links between `posts`; links to anything that is not one of `posts`, and links of a post to itself, are left out.
+    ///
+    /// `posts` are expected to be sorted newest first, backlinks keep this order.
+    pub fn new(posts: &[&'a Post]) -> Self {
+        let mut backlinks: HashMap<String, Vec<&'a PostMeta>> = posts
+            .iter()
+            .map(|post| (normalize(&post.meta.source_file), Vec::new()))
+            .collect();
+
+        for post in posts {
+            let source = normalize(&post.meta.source_file);
+            for link in post.links.iter().filter(|link| **link != source) {
+                if let Some(linking) = backlinks.get_mut(link) {
+                    linking.push(&post.meta);
+                }
+            }
+        }
+
+        LinkGraph {
+            posts: posts.to_vec(),
+            backlinks,
+        }
+    }
+
+    /// Posts linking to the post described by `meta`
+    pub fn backlinks(&self, meta: &PostMeta) -> Vec<&'a PostMeta> {
+        self.backlinks
+            .get(&normalize(&meta.source_file))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn nodes(&self) -> Vec<GraphNode<'a>> {
+        self.posts
+            .iter()
+            .map(|post| GraphNode {
+                id: normalize(&post.meta.source_file),
+                title: post.meta.header.as_ref().and_then(|h| h.title.as_deref()),
+                url: format!("/{}", normalize(&post.meta.rendered_to)),
+            })
+            .collect()
+    }
+
+    fn edges(&self) -> Vec<GraphEdge> {
+        let mut edges = Vec::new();
+        for post in &self.posts {
+            let source = normalize(&post.meta.source_file);
+            for link in &post.links {
+                if *link != source && self.backlinks.contains_key(link) {
+                    edges.push(GraphEdge {
+                        source: source.clone(),
+                        target: link.clone(),
+                    });
+                }
+            }
+        }
+        edges
+    }
+
+    /// Render the graph as JSON object of `nodes` ([GraphNode]) and `edges` ([GraphEdge]) into [GRAPH_FILE]
+    pub fn render_graph(&self) -> Result<OutputFile, serde_json::Error> {
+        #[derive(Serialize)]
+        struct Graph<'a> {
+            nodes: Vec<GraphNode<'a>>,
+            edges: Vec<GraphEdge>,
+        }
+
+        let content = serde_json::to_string(&Graph {
+            nodes: self.nodes(),
+            edges: self.edges(),
+        })?;
+
+        Ok(OutputFile {
+            path: GRAPH_FILE.to_string(),
+            content,
+        })
+    }
+}
@@ -0,0 +1,195 @@
+use std::error::Error;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Color, Theme, ThemeSet};
+use syntect::html::{
+    css_for_theme_with_class_style, line_tokens_to_classed_spans, styled_line_to_highlighted_html,
+    ClassStyle, IncludeBackground,
+};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// prefix of the css classes used with [HighlightStyle::Classes], to avoid clashes with site styles
+pub const CLASS_PREFIX: &str = "hl-";
+
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed {
+    prefix: CLASS_PREFIX,
+};
+
+/// How highlighted code is styled
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightStyle {
+    /// colors from the theme as inline styles
+    #[default]
+    Inline,
+    /// css classes, styled by a stylesheet exported using [theme_css]
+    Classes,
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+    THEME_SET.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Look up one of the themes bundled with syntect, e.g. `base16-ocean.dark`
+pub fn theme(name: &str) -> Result<&'static Theme, String> {
+    theme_set().themes.get(name).ok_or_else(|| {
+        let available: Vec<&str> = theme_set().themes.keys().map(String::as_str).collect();
+        format!(
+            "Unknown highlighting theme {name}, available themes: {}",
+            available.join(", ")
+        )
+    })
+}
+
+/// Stylesheet for code highlighted using [HighlightStyle::Classes]
+///
+/// # Examples
+///
+/// ```
+/// use markhor::highlight::theme_css;
+///
+/// let css = theme_css("InspiredGitHub").unwrap();
+/// assert!(css.contains(".hl-code {"));
+///
+/// assert!(theme_css("missing").is_err());
+/// ```
+pub fn theme_css(theme_name: &str) -> Result<String, Box<dyn Error>> {
+    Ok(css_for_theme_with_class_style(
+        theme(theme_name)?,
+        CLASS_STYLE,
+    )?)
+}
+
+/// Find the syntax for the language of a code block, e.g. `rust` or `rs`
+fn find_syntax(language: &str) -> Option<&'static SyntaxReference> {
+    syntax_set().find_syntax_by_token(language)
+}
+
+fn css_color(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Highlight `code` line by line. Every line is self-contained html without the line break,
+/// spans never cross lines.
+///
+/// Returns `None` if the language or theme is unknown, or highlighting fails.
+pub fn highlight_lines(
+    code: &str,
+    language: &str,
+    style: HighlightStyle,
+    theme_name: &str,
+) -> Option<Vec<String>> {
+    let syntax = find_syntax(language)?;
+
+    match style {
+        HighlightStyle::Inline => {
+            let mut highlighter = HighlightLines::new(syntax, theme(theme_name).ok()?);
+
+            LinesWithEndings::from(code)
+                .map(|line| {
+                    let mut regions = highlighter.highlight_line(line, syntax_set()).ok()?;
+                    if let Some((_, text)) = regions.last_mut() {
+                        *text = text.trim_end_matches(['\r', '\n']);
+                    }
+                    styled_line_to_highlighted_html(&regions, IncludeBackground::No).ok()
+                })
+                .collect()
+        }
+        HighlightStyle::Classes => {
+            let mut parse_state = ParseState::new(syntax);
+            let mut stack = ScopeStack::new();
+
+            LinesWithEndings::from(code)
+                .map(|line| {
+                    // reopen the spans still open from previous lines
+                    let mut html = String::new();
+                    for scope in stack.as_slice() {
+                        let classes: Vec<String> = scope
+                            .build_string()
+                            .split('.')
+                            .map(|atom| format!("{CLASS_PREFIX}{atom}"))
+                            .collect();
+                        html.push_str(&format!(r#"<span class="{}">"#, classes.join(" ")));
+                    }
+
+                    let ops = parse_state.parse_line(line, syntax_set()).ok()?;
+                    let (spans, _) =
+                        line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut stack).ok()?;
+                    // the line break can end up inside a span, e.g. of a line comment
+                    html.push_str(&spans.replace(['\r', '\n'], ""));
+
+                    html.push_str(&"</span>".repeat(stack.len()));
+                    Some(html)
+                })
+                .collect()
+        }
+    }
+}
+
+/// Opening `<pre>` tag for code highlighted using `style`, with the theme colors for [HighlightStyle::Inline]
+///
+/// Returns `None` if the theme is unknown.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::highlight::{highlight_lines, pre_tag, HighlightStyle};
+///
+/// assert_eq!(pre_tag(HighlightStyle::Classes, "InspiredGitHub").unwrap(), r#"<pre class="highlight hl-code">"#);
+/// assert_eq!(
+///     pre_tag(HighlightStyle::Inline, "InspiredGitHub").unwrap(),
+///     r#"<pre class="highlight" style="color:#323232;background-color:#ffffff;">"#
+/// );
+///
+/// let lines = highlight_lines("let x = 1;\n", "rust", HighlightStyle::Classes, "InspiredGitHub").unwrap();
+/// assert!(lines[0].contains(r#"<span class="hl-storage hl-type hl-rust">let</span>"#));
+///
+/// assert_eq!(highlight_lines("code", "no-such-language", HighlightStyle::Inline, "InspiredGitHub"), None);
+/// ```
+pub fn pre_tag(style: HighlightStyle, theme_name: &str) -> Option<String> {
+    match style {
+        HighlightStyle::Inline => {
+            let settings = &theme(theme_name).ok()?.settings;
+            let mut css = String::new();
+            if let Some(foreground) = settings.foreground {
+                css.push_str(&format!("color:{};", css_color(foreground)));
+            }
+            if let Some(background) = settings.background {
+                css.push_str(&format!("background-color:{};", css_color(background)));
+            }
+            Some(format!(r#"<pre class="highlight" style="{css}">"#))
+        }
+        HighlightStyle::Classes => Some(format!(r#"<pre class="highlight {CLASS_PREFIX}code">"#)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{highlight_lines, HighlightStyle};
+
+    #[test]
+    fn test_highlight_lines_should_drop_line_breaks_in_comments() {
+        for style in [HighlightStyle::Classes, HighlightStyle::Inline] {
+            let lines = highlight_lines(
+                "let x = 1; // comment\nlet y = 2;\n",
+                "rust",
+                style,
+                "InspiredGitHub",
+            )
+            .unwrap();
+
+            assert_eq!(lines.len(), 2);
+            assert!(lines[0].contains("comment"));
+            assert!(lines.iter().all(|line| !line.contains('\n')));
+        }
+    }
+}
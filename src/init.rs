@@ -0,0 +1,153 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use log::info;
+
+use crate::config::CONFIG_FILE;
+
+/// Files of a minimal working site, as (path relative to the site root, content)
+pub const SKELETON: &[(&str, &str)] = &[
+    (CONFIG_FILE, include_str!("init/markhor.toml")),
+    (
+        "posts/hello-world.md",
+        include_str!("init/posts/hello-world.md"),
+    ),
+    ("pages/about.md", include_str!("init/pages/about.md")),
+    ("static/style.css", include_str!("init/static/style.css")),
+    (
+        "templates/base.html",
+        include_str!("init/templates/base.html"),
+    ),
+    (
+        "templates/index.html",
+        include_str!("init/templates/index.html"),
+    ),
+    (
+        "templates/post.html",
+        include_str!("init/templates/post.html"),
+    ),
+    (
+        "templates/category.html",
+        include_str!("init/templates/category.html"),
+    ),
+    (
+        "templates/page.html",
+        include_str!("init/templates/page.html"),
+    ),
+    (
+        "templates/tag.html",
+        include_str!("init/templates/tag.html"),
+    ),
+    (
+        "templates/tags.html",
+        include_str!("init/templates/tags.html"),
+    ),
+    (
+        "templates/archive.html",
+        include_str!("init/templates/archive.html"),
+    ),
+    (
+        "templates/series.html",
+        include_str!("init/templates/series.html"),
+    ),
+    (
+        "templates/shortcodes/note.html",
+        include_str!("init/templates/shortcodes/note.html"),
+    ),
+];
+
+/// Write the [SKELETON] site into `site_dir`.
+///
+/// Fails without writing anything if any of the files already exists.
+pub fn init_site(site_dir: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+    let site_dir = site_dir.as_ref();
+
+    let existing: Vec<String> = SKELETON
+        .iter()
+        .map(|(path, _)| site_dir.join(path))
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .collect();
+
+    if !existing.is_empty() {
+        return Err(format!(
+            "Refusing to overwrite existing files: {}",
+            existing.join(", ")
+        )
+        .into());
+    }
+
+    for (path, content) in SKELETON {
+        let path = site_dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        info!("Creating {}", path.display());
+        fs::write(path, content)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::init_site;
+    use crate::config::Config;
+    use crate::test_utils::TempDir;
+    use crate::{check_site, render_site};
+
+    #[test]
+    fn test_init_site_should_build() {
+        let site_dir = TempDir::new("init");
+
+        init_site(&site_dir).expect("failed to init site");
+
+        let config = Config::load(&site_dir).unwrap();
+        let num_files = check_site(&config, false).expect("skeleton site does not build");
+        // post, page, category page, two tag pages, tag index, yearly and monthly archive, index
+        // Atom, RSS and JSON feeds for the site and the category, sitemap, robots.txt and link graph
+        assert_eq!(num_files, 18);
+
+        assert!(init_site(&site_dir).is_err());
+    }
+
+    #[test]
+    fn test_init_site_should_paginate_categories() {
+        let site_dir = TempDir::new("init-pages");
+
+        init_site(&site_dir).expect("failed to init site");
+        // together with hello-world.md, one more than paginate_by
+        for day in 1..=10 {
+            site_dir.write(
+                &format!("posts/post-{day}.md"),
+                format!(
+                    "---\ntitle = \"Post {day}\"\ndate = \"2022-02-{day:02}\"\ncategory = \"general\"\n---\nText"
+                ),
+            );
+        }
+
+        let config = Config::load(&site_dir).unwrap();
+        let files = render_site(&config, false).unwrap();
+        let page = |path: &str| {
+            &files
+                .iter()
+                .find(|file| file.path == path)
+                .unwrap_or_else(|| panic!("{path} not rendered"))
+                .content
+        };
+
+        let first = page("general.html");
+        assert_eq!(first.matches("<li>").count(), 10);
+        assert!(first.contains(r#"<a href="/general/post-10.html">Post 10</a>"#));
+        assert!(!first.contains("Hello world"));
+        assert!(first.contains("Page 1 of 2"));
+        assert!(first.contains(r#"<a href="/general/page/2/index.html">Older posts</a>"#));
+
+        let second = page("general/page/2/index.html");
+        assert_eq!(second.matches("<li>").count(), 1);
+        assert!(second.contains(r#"<a href="/general/hello-world.html">Hello world</a>"#));
+        assert!(second.contains("Page 2 of 2"));
+        assert!(second.contains(r#"<a href="/general.html">Newer posts</a>"#));
+    }
+}
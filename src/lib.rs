@@ -1,13 +1,17 @@
-use chrono::NaiveDate;
+use chrono::{Local, NaiveDate};
+use config::Config;
+use export::render_json_export;
+use feed::render_feeds;
 use fs_extra::{copy_items, dir};
 use log::info;
 
-use markdown::convert_posts;
+use markdown::{convert_pages, convert_posts, format_header};
 use serde::{Deserialize, Serialize};
-use templating::{render_index, values};
+use templating::{render_index, templates, values};
 use tera::Context;
 
 use std::cmp::Ordering::{self, Equal, Greater, Less};
+use std::collections::{BTreeSet, HashSet};
 use std::io::Write;
 use std::ops::Sub;
 use std::path::{Path, PathBuf};
@@ -18,27 +22,80 @@ use std::{
     io,
 };
 
-use crate::templating::{render_category_page, render_post_into_template};
+use crate::archive::{collect_archives, posts_in_archive};
+use crate::graph::LinkGraph;
+use crate::links::{broken_links_error, normalize};
+use crate::pagination::paginate;
+use crate::related::RelatedPosts;
+use crate::search::render_search_index;
+use crate::series::{collect_series, Series};
+use crate::sitemap::{priorities, render_sitemap, SitemapEntry};
+use crate::tags::{collect_tags, Tag, TAGS_DIR};
+use crate::templating::{
+    has_template, render_archive_page, render_category_page, render_page_into_template,
+    render_post_into_template, render_series_page, render_tag_index, render_tag_page,
+};
 
+pub mod archive;
+pub mod codeblock;
+pub mod config;
+pub mod export;
+pub mod feed;
+pub mod graph;
+pub mod highlight;
+pub mod init;
+pub mod links;
 pub mod markdown;
+pub mod pagination;
+pub mod permalink;
+pub mod related;
+pub mod search;
+pub mod series;
 pub mod serve;
+pub mod shortcodes;
+pub mod sitemap;
+pub mod tags;
 pub mod templating;
 pub mod watch;
 
+#[cfg(test)]
+mod test_utils;
+
+/// format of [PostHeader::date]
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
 /// PostHeader represents metadata added at the start of a markdown post.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
 pub struct PostHeader {
     pub title: Option<String>,
     pub date: Option<String>,
     pub category: Option<String>,
     pub draft: Option<bool>,
+    /// date of the last significant change, same format as `date`
+    pub updated: Option<String>,
+    /// render the post, but leave it out of post listings, feeds and the sitemap
+    pub unlisted: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    /// name of the series this post is a part of, see [`series::collect_series`]
+    pub series: Option<String>,
+    /// position in the series, parts without one come after the ordered ones
+    pub series_order: Option<u32>,
+    /// output name, used instead of the file name
+    pub slug: Option<String>,
+    /// template used instead of the default one
+    pub template: Option<String>,
+    /// any other values, available in templates next to the known fields (e.g. `header.subtitle`)
+    #[serde(flatten)]
+    pub extra: toml::value::Table,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Default)]
 pub struct Post {
     pub meta: PostMeta,
     pub content: String,
     pub headings: Vec<TocHeading>,
+    /// [PostMeta::source_file] of every post linked from this one, see [`links::LinkTargets`]
+    pub links: Vec<String>,
 }
 
 /// Table of contents entry for posts
@@ -47,6 +104,8 @@ pub struct TocHeading {
     pub level: u8,
     pub prev_level: Option<u8>,
     pub text: String,
+    /// unique id of the heading element, see [`markdown::heading_id`]
+    pub id: String,
 }
 
 ///
@@ -57,11 +116,11 @@ pub struct TocHeading {
 /// use std::cmp::Ordering::{self, Equal, Less, Greater};
 /// use markhor::compare_header_date;
 ///
-/// let a = PostHeader {title: None, date: Some("1900-01-01".to_string()), category: None, draft:  None};
-/// let b = PostHeader {title: None, date: Some("2022-01-01".to_string()), category: None, draft:  None};
-/// let c = PostHeader {title: None, date: Some("3333-01-01".to_string()), category: None, draft:  None};
-/// let d = PostHeader {title: None, date: Some("1900-01-01".to_string()), category: None, draft:  None};
-/// let e = PostHeader {title: None, date: None, category: None, draft: None};
+/// let a = PostHeader {date: Some("1900-01-01".to_string()), ..Default::default()};
+/// let b = PostHeader {date: Some("2022-01-01".to_string()), ..Default::default()};
+/// let c = PostHeader {date: Some("3333-01-01".to_string()), ..Default::default()};
+/// let d = PostHeader {date: Some("1900-01-01".to_string()), ..Default::default()};
+/// let e = PostHeader::default();
 ///
 /// assert_eq!(compare_header_date(&a, &b), Less);
 /// assert_eq!(compare_header_date(&a, &c), Less);
@@ -75,15 +134,54 @@ pub fn compare_header_date(a: &PostHeader, b: &PostHeader) -> Ordering {
     let db = b.date.as_ref();
 
     compare_option(&da, &db, |a, b| {
-        let date_a = NaiveDate::parse_from_str(a, "%Y-%m-%d").expect("failed to parse date");
-        let date_b = NaiveDate::parse_from_str(b, "%Y-%m-%d").expect("failed to parse date");
+        let date_a = NaiveDate::parse_from_str(a, DATE_FORMAT).expect("failed to parse date");
+        let date_b = NaiveDate::parse_from_str(b, DATE_FORMAT).expect("failed to parse date");
 
         date_a.cmp(&date_b)
     })
 }
 
+/// The posts before and after `post` in `posts`, as `(previous, next)`.
+///
+/// `posts` are expected to be sorted newest first (like `posts_meta`), so the previous post is
+/// the older one and the next post the newer one. Both are `None` if `post` is not in `posts`.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::{adjacent_posts, PostMeta};
+///
+/// let post = |name: &str| PostMeta {
+///     source_file: format!("{name}.md"),
+///     rendered_to: format!("{name}.html"),
+///     ..Default::default()
+/// };
+/// let (newest, middle, oldest, unlisted) = (post("newest"), post("middle"), post("oldest"), post("unlisted"));
+/// let posts = [&newest, &middle, &oldest];
+///
+/// assert_eq!(adjacent_posts(&posts, &middle), (Some(&oldest), Some(&newest)));
+/// assert_eq!(adjacent_posts(&posts, &newest), (Some(&middle), None));
+/// assert_eq!(adjacent_posts(&posts, &oldest), (None, Some(&middle)));
+/// assert_eq!(adjacent_posts(&posts, &unlisted), (None, None));
+/// ```
+pub fn adjacent_posts<'a>(
+    posts: &[&'a PostMeta],
+    post: &PostMeta,
+) -> (Option<&'a PostMeta>, Option<&'a PostMeta>) {
+    match posts
+        .iter()
+        .position(|meta| meta.rendered_to == post.rendered_to)
+    {
+        Some(index) => (
+            posts.get(index + 1).copied(),
+            index.checked_sub(1).map(|newer| posts[newer]),
+        ),
+        None => (None, None),
+    }
+}
+
 /// PostMeta contains post metadata originated from the build process and the optional [PostHeader]
-#[derive(Debug, Serialize, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, Serialize, PartialEq, Clone, Default)]
 pub struct PostMeta {
     /// the markdown file used as content source
     pub source_file: String,
@@ -95,64 +193,518 @@ pub struct PostMeta {
     pub preview_text: String,
 }
 
-pub fn generate_site<P>(
-    templates_glob: &str,
-    posts_dir: P,
-    output_dir: P,
-    drafts: bool,
-) -> Result<(), Box<dyn Error>>
-where
-    P: AsRef<Path> + Copy,
-{
+/// A rendered file, relative to the output directory
+pub struct OutputFile {
+    pub path: String,
+    pub content: String,
+}
+
+impl PostHeader {
+    /// whether the post is only rendered when building drafts
+    pub fn is_draft(&self) -> bool {
+        self.draft.unwrap_or(false)
+    }
+
+    /// Check that `date` and `updated` are valid dates in [DATE_FORMAT].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use markhor::PostHeader;
+    ///
+    /// let header = PostHeader {date: Some("2022-03-01".to_string()), updated: Some("2022-03-05".to_string()), ..Default::default()};
+    /// assert!(header.check_dates().is_ok());
+    ///
+    /// let header = PostHeader {updated: Some("2022-02-30".to_string()), ..Default::default()};
+    /// assert!(header.check_dates().is_err());
+    /// ```
+    pub fn check_dates(&self) -> Result<(), String> {
+        for (key, value) in [("date", &self.date), ("updated", &self.updated)] {
+            if let Some(value) = value {
+                NaiveDate::parse_from_str(value, DATE_FORMAT)
+                    .map_err(|e| format!("failed to parse {key} {value:?}: {e}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PostMeta {
+    /// whether the post is only rendered when building drafts, see [PostHeader::is_draft]
+    pub fn is_draft(&self) -> bool {
+        self.header.as_ref().is_some_and(PostHeader::is_draft)
+    }
+
+    /// whether the post appears in post listings, feeds and the sitemap
+    pub fn is_listed(&self) -> bool {
+        !self
+            .header
+            .as_ref()
+            .is_some_and(|h| h.unlisted.unwrap_or(false))
+    }
+}
+
+/// Render the site and write the result to [Config::output_dir]
+pub fn generate_site(config: &Config, drafts: bool) -> Result<(), Box<dyn Error>> {
     let start_time = Instant::now();
 
-    let mut tera = templating::init_tera(templates_glob);
+    let output_dir = config.output_dir();
 
-    let posts_by_cat = convert_posts(posts_dir, drafts)?;
+    for file in render_site(config, drafts)? {
+        write_output(&output_dir, file.path, file.content)?;
+    }
 
-    let mut sorted_meta: Vec<&PostMeta> = posts_by_cat
-        .iter()
-        .flat_map(|(_, postvec)| postvec.iter().map(|post| &post.meta))
-        .collect();
-    sorted_meta.sort_unstable_by(|a, b| {
-        compare_option(&b.header, &a.header, |meta_a, meta_b| {
+    let elapsed_time = Instant::now().sub(start_time);
+    log::info!("Took {}ms", &elapsed_time.as_millis());
+    Ok(())
+}
+
+/// Render the site without writing any output, returning the number of generated files
+pub fn check_site(config: &Config, drafts: bool) -> Result<usize, Box<dyn Error>> {
+    let files = render_site(config, drafts)?;
+
+    Ok(files.len())
+}
+
+/// Convert all posts and render them into their templates
+pub fn render_site(config: &Config, drafts: bool) -> Result<Vec<OutputFile>, Box<dyn Error>> {
+    let mut tera = templating::init_tera(&config.templates_glob())?;
+
+    if config.markdown.highlight {
+        // fail early instead of silently rendering code blocks without highlighting
+        highlight::theme(&config.markdown.highlight_theme)?;
+    }
+
+    // posts and pages are both checked before reporting their broken links together
+    let mut broken_links = Vec::new();
+    let converted = convert_posts(
+        config.posts_dir(),
+        drafts,
+        config.mirror_directories,
+        config.permalink.as_deref(),
+        &config.markdown,
+        &mut tera,
+        &mut broken_links,
+    )?;
+    let pages = convert_pages(
+        config.pages_dir(),
+        drafts,
+        &config.markdown,
+        &mut tera,
+        &converted.link_targets,
+        &mut broken_links,
+    )?;
+    if !broken_links.is_empty() {
+        return Err(broken_links_error(&broken_links).into());
+    }
+    let posts_by_cat = converted.posts;
+
+    let mut sorted_posts: Vec<&Post> = posts_by_cat.values().flatten().collect();
+    sorted_posts.sort_unstable_by(|a, b| {
+        compare_option(&b.meta.header, &a.meta.header, |meta_a, meta_b| {
             compare_header_date(meta_a, meta_b)
         })
     });
+    sorted_posts.retain(|post| post.meta.is_listed());
+    let sorted_meta: Vec<&PostMeta> = sorted_posts.iter().map(|post| &post.meta).collect();
+
+    let categories: Vec<&Option<String>> = posts_by_cat.keys().collect();
 
-    let categories: Vec<&Option<String>> = posts_by_cat.keys().into_iter().collect();
+    let posts_by_tag = collect_tags(&sorted_meta)?;
+    let tags: Vec<&Tag> = posts_by_tag.iter().map(|(tag, _)| tag).collect();
+
+    let archives = collect_archives(&sorted_meta);
+    let series = collect_series(&sorted_meta)?;
+    let related_posts = config
+        .related_posts
+        .map(|count| RelatedPosts::new(&sorted_posts, count));
+    let link_graph = LinkGraph::new(&sorted_posts);
+
+    let pages_meta: Vec<&PostMeta> = pages.iter().map(|page| &page.meta).collect();
 
     let mut context = Context::new();
+    context.insert(values::SITE, config);
     context.insert(values::POSTS_META, &sorted_meta);
     context.insert(values::POST_CATEGORIES, &categories);
+    context.insert(values::POST_TAGS, &tags);
+    context.insert(values::PAGES, &pages_meta);
+    context.insert(values::ARCHIVES, &archives);
+
+    let mut files = Vec::new();
+
+    for page in &pages {
+        let result_html = render_page_into_template(&mut tera, &mut context, page)?;
+
+        files.push(OutputFile {
+            path: page.meta.rendered_to.clone(),
+            content: result_html,
+        });
+    }
+
+    context.remove(values::POST_CONTENT);
+    context.remove(values::HEADER);
 
     for (category, posts) in &posts_by_cat {
         info!("Rendering category: {:?}", category);
 
+        let category_meta: Vec<&PostMeta> = sorted_meta
+            .iter()
+            .filter(|meta| {
+                meta.header.as_ref().and_then(|h| h.category.as_ref()) == category.as_ref()
+            })
+            .copied()
+            .collect();
+
         for post in posts {
             let meta = &post.meta;
+
+            let (prev_post, next_post) = adjacent_posts(&sorted_meta, meta);
+            context.insert(values::PREV_POST, &prev_post);
+            context.insert(values::NEXT_POST, &next_post);
+            let (prev_in_category, next_in_category) = adjacent_posts(&category_meta, meta);
+            context.insert(values::PREV_IN_CATEGORY, &prev_in_category);
+            context.insert(values::NEXT_IN_CATEGORY, &next_in_category);
+            let post_series = series
+                .iter()
+                .find_map(|series| series.position(meta).map(|position| (series, position)));
+            if let Some((series, position)) = post_series {
+                context.insert(values::SERIES, series);
+                context.insert(values::SERIES_POSITION, &position);
+            } else {
+                context.remove(values::SERIES);
+                context.remove(values::SERIES_POSITION);
+            }
+            if let Some(related_posts) = &related_posts {
+                context.insert(values::RELATED_POSTS, &related_posts.related(post));
+            }
+            context.insert(values::BACKLINKS, &link_graph.backlinks(meta));
+
             let result_html = render_post_into_template(&mut tera, &mut context, post)?;
 
-            write_output(output_dir, &meta.rendered_to, result_html)?;
+            files.push(OutputFile {
+                path: meta.rendered_to.clone(),
+                content: result_html,
+            });
         }
 
         context.remove(values::POST_CONTENT);
         context.remove(values::HEADER);
+        context.remove(values::PREV_POST);
+        context.remove(values::NEXT_POST);
+        context.remove(values::PREV_IN_CATEGORY);
+        context.remove(values::NEXT_IN_CATEGORY);
+        context.remove(values::RELATED_POSTS);
+        context.remove(values::BACKLINKS);
+        context.remove(values::SERIES);
+        context.remove(values::SERIES_POSITION);
 
         if let Some(cat) = category {
-            let category_page_html = render_category_page(&tera, &mut context, cat, posts)?;
+            // newest first, like the index
+            let listed_posts: Vec<&Post> = sorted_posts
+                .iter()
+                .filter(|post| {
+                    post.meta.header.as_ref().and_then(|h| h.category.as_ref()) == Some(cat)
+                })
+                .copied()
+                .collect();
+            // metadata like on the index, so `paginator.posts` has the same shape everywhere
+            let listed_meta: Vec<&PostMeta> = listed_posts.iter().map(|post| &post.meta).collect();
+            let first_page = format!("{cat}.html");
+
+            for paginator in paginate(&listed_meta, config.paginate_by, &first_page, cat) {
+                context.insert(values::PAGINATOR, &paginator);
+                let category_page_html =
+                    render_category_page(&tera, &mut context, cat, &listed_posts)?;
+
+                files.push(OutputFile {
+                    path: paginator.path,
+                    content: category_page_html,
+                });
+            }
+            context.remove(values::PAGINATOR);
+        }
+    }
 
-            let category_out_file = format!("{cat}.html");
-            write_output(output_dir, category_out_file, category_page_html)?;
+    // tag and series pages are optional, so older template sets keep building
+    let tag_pages: &[(Tag, Vec<&PostMeta>)] = if has_template(&tera, templates::TAG) {
+        &posts_by_tag
+    } else {
+        &[]
+    };
+    let series_pages: &[Series] = if has_template(&tera, templates::SERIES) {
+        &series
+    } else {
+        &[]
+    };
+    let tag_index = !posts_by_tag.is_empty() && has_template(&tera, templates::TAG_INDEX);
+
+    for (tag, posts) in tag_pages {
+        info!("Rendering tag: {}", tag.name);
+
+        let tag_page_html = render_tag_page(&tera, &mut context, tag, posts)?;
+
+        files.push(OutputFile {
+            path: tag.rendered_to.clone(),
+            content: tag_page_html,
+        });
+    }
+
+    for series in series_pages {
+        info!("Rendering series: {}", series.name);
+
+        files.push(OutputFile {
+            path: series.rendered_to.clone(),
+            content: render_series_page(&tera, &mut context, series)?,
+        });
+    }
+
+    if tag_index {
+        files.push(OutputFile {
+            path: format!("{TAGS_DIR}/index.html"),
+            content: render_tag_index(&tera, &mut context)?,
+        });
+    }
+
+    if has_template(&tera, templates::ARCHIVE) {
+        for year in &archives {
+            info!("Rendering archive: {}", year.year);
+
+            let posts = posts_in_archive(&sorted_meta, year.year, None);
+            files.push(OutputFile {
+                path: year.rendered_to.clone(),
+                content: render_archive_page(&tera, &mut context, year, None, &posts)?,
+            });
+
+            for month in &year.months {
+                let posts = posts_in_archive(&sorted_meta, year.year, Some(month.month));
+                files.push(OutputFile {
+                    path: month.rendered_to.clone(),
+                    content: render_archive_page(&tera, &mut context, year, Some(month), &posts)?,
+                });
+            }
         }
     }
 
-    let index_html = render_index(&tera, &mut context)?;
+    for paginator in paginate(&sorted_meta, config.paginate_by, "index.html", "") {
+        context.insert(values::PAGINATOR, &paginator);
+        let index_html = render_index(&tera, &mut context)?;
 
-    write_output(output_dir, "index.html", index_html)?;
+        files.push(OutputFile {
+            path: paginator.path,
+            content: index_html,
+        });
+    }
+    context.remove(values::PAGINATOR);
+
+    if config.json_export {
+        files.extend(render_json_export(&sorted_posts)?);
+    }
+
+    if config.search.enabled {
+        let published_posts: Vec<&Post> = sorted_posts
+            .iter()
+            .filter(|post| !post.meta.is_draft())
+            .copied()
+            .collect();
+        files.push(render_search_index(
+            &published_posts,
+            config.search.inverted_index,
+        )?);
+    }
+
+    files.push(link_graph.render_graph()?);
+
+    let site_title = config.title.as_deref().unwrap_or_default();
+    files.extend(render_feeds(config, site_title, "", &sorted_posts)?);
+
+    if config.feeds.categories {
+        for category in posts_by_cat.keys().flatten() {
+            let posts_in_category: Vec<&Post> = sorted_posts
+                .iter()
+                .filter(|post| {
+                    post.meta.header.as_ref().and_then(|h| h.category.as_ref()) == Some(category)
+                })
+                .copied()
+                .collect();
+
+            let title = format!("{site_title} - {category}");
+            files.extend(render_feeds(config, &title, category, &posts_in_category)?);
+        }
+    }
+
+    files.extend(render_sitemap(
+        config,
+        &sitemap_entries(
+            &sorted_meta,
+            &pages_meta,
+            tag_pages,
+            tag_index,
+            series_pages,
+        ),
+    ));
+
+    let all_posts: Vec<&PostMeta> = posts_by_cat
+        .values()
+        .flatten()
+        .map(|post| &post.meta)
+        .collect();
+    check_output_paths(&files, &all_posts, &pages_meta)?;
+
+    Ok(files)
+}
+
+/// Make sure no two `files` are written to the same path, e.g. by `pages/index.md` and the index.
+///
+/// The error names the `posts` and `pages` rendered to each duplicate path.
+fn check_output_paths(
+    files: &[OutputFile],
+    posts: &[&PostMeta],
+    pages: &[&PostMeta],
+) -> Result<(), String> {
+    let mut paths = HashSet::new();
+    let duplicates: BTreeSet<String> = files
+        .iter()
+        .map(|file| normalize(&file.path))
+        .filter(|path| !paths.insert(path.clone()))
+        .collect();
+
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = String::from("Found files that would be written more than once:");
+    for path in duplicates {
+        let rendered_from: Vec<String> = [("post", posts), ("page", pages)]
+            .iter()
+            .flat_map(|(kind, sources)| {
+                sources
+                    .iter()
+                    .filter(|meta| normalize(&meta.rendered_to) == path)
+                    .map(move |meta| format!("{kind} {}", meta.source_file))
+            })
+            .collect();
+        message.push_str(&format!("\n  {path}"));
+        if !rendered_from.is_empty() {
+            message.push_str(&format!(" ({})", rendered_from.join(", ")));
+        }
+    }
+    Err(message)
+}
+
+/// Every listed, non-draft post and page, the category, tag and series pages and the index
+///
+/// `sorted_meta` only contains listed posts, `posts_by_tag` and `series` only the rendered tag and series pages
+fn sitemap_entries(
+    sorted_meta: &[&PostMeta],
+    pages_meta: &[&PostMeta],
+    posts_by_tag: &[(Tag, Vec<&PostMeta>)],
+    tag_index: bool,
+    series: &[Series],
+) -> Vec<SitemapEntry> {
+    let published_posts = sorted_meta.iter().filter(|meta| !meta.is_draft());
+    // the newest date of a set of posts, as they are sorted newest first
+    let newest = |posts: &[&PostMeta]| {
+        posts
+            .iter()
+            .find(|meta| !meta.is_draft())
+            .and_then(|meta| SitemapEntry::for_post(meta, 0.0).lastmod)
+    };
+
+    let mut entries = vec![SitemapEntry {
+        path: "index.html".to_string(),
+        lastmod: newest(sorted_meta),
+        priority: priorities::INDEX,
+    }];
+
+    entries.extend(published_posts.map(|meta| SitemapEntry::for_post(meta, priorities::POST)));
+    entries.extend(
+        pages_meta
+            .iter()
+            .filter(|meta| !meta.is_draft() && meta.is_listed())
+            .map(|meta| SitemapEntry::for_post(meta, priorities::PAGE)),
+    );
+
+    let mut categories: Vec<&String> = sorted_meta
+        .iter()
+        .filter_map(|meta| meta.header.as_ref().and_then(|h| h.category.as_ref()))
+        .collect();
+    categories.sort();
+    categories.dedup();
+    for category in categories {
+        let posts_in_category: Vec<&PostMeta> = sorted_meta
+            .iter()
+            .filter(|meta| meta.header.as_ref().and_then(|h| h.category.as_ref()) == Some(category))
+            .copied()
+            .collect();
+        entries.push(SitemapEntry {
+            path: format!("{category}.html"),
+            lastmod: newest(&posts_in_category),
+            priority: priorities::CATEGORY,
+        });
+    }
+
+    for (tag, posts) in posts_by_tag {
+        entries.push(SitemapEntry {
+            path: tag.rendered_to.clone(),
+            lastmod: newest(posts),
+            priority: priorities::TAG,
+        });
+    }
+    if tag_index {
+        entries.push(SitemapEntry {
+            path: format!("{TAGS_DIR}/index.html"),
+            lastmod: newest(sorted_meta),
+            priority: priorities::TAG,
+        });
+    }
+
+    for series in series {
+        // parts are in reading order, so look for the newest date
+        let lastmod = series
+            .parts
+            .iter()
+            .filter(|meta| !meta.is_draft())
+            .filter_map(|meta| SitemapEntry::for_post(meta, 0.0).lastmod)
+            .max();
+        entries.push(SitemapEntry {
+            path: series.rendered_to.clone(),
+            lastmod,
+            priority: priorities::SERIES,
+        });
+    }
+
+    entries
+}
+
+/// Remove [Config::output_dir] and everything in it
+///
+/// Refuses to remove the site root, any of its parents or a directory that contains site sources
+/// (`posts`, `templates` or [CONFIG_FILE](config::CONFIG_FILE)), e.g. because of `output = "."`.
+pub fn clean_output(config: &Config) -> Result<(), Box<dyn Error>> {
+    let output_dir = config.output_dir();
+
+    if !output_dir.is_dir() {
+        info!("{} does not exist, nothing to clean", output_dir.display());
+        return Ok(());
+    }
+
+    let canonical_output = output_dir.canonicalize()?;
+    let canonical_root = Path::new(".").join(&config.root).canonicalize()?;
+    let contains_sources = ["posts", "templates", config::CONFIG_FILE]
+        .iter()
+        .any(|name| canonical_output.join(name).exists());
+
+    if canonical_root.starts_with(&canonical_output) || contains_sources {
+        return Err(format!(
+            "Refusing to remove {}, it contains the site sources",
+            output_dir.display()
+        )
+        .into());
+    }
+
+    info!("Removing {}", output_dir.display());
+    fs::remove_dir_all(output_dir)?;
 
-    let elapsed_time = Instant::now().sub(start_time);
-    log::info!("Took {}ms", &elapsed_time.as_millis());
     Ok(())
 }
 
@@ -196,6 +748,93 @@ pub fn write_output(
     Ok(())
 }
 
+/// Create a new markdown file for a post in `posts_dir`, named after the slugified title.
+///
+/// The file starts with a [PostHeader] dated today. Returns the path of the new file.
+pub fn new_post(
+    posts_dir: impl AsRef<Path>,
+    title: &str,
+    category: Option<String>,
+    draft: bool,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let slug = slugify(title);
+    if slug.is_empty() {
+        return Err(format!("Can not create a file name from title {:?}", title).into());
+    }
+
+    let mut filepath = PathBuf::from(posts_dir.as_ref());
+    filepath.push(format!("{slug}.md"));
+
+    if filepath.exists() {
+        return Err(format!("{} already exists", filepath.display()).into());
+    }
+
+    let header = PostHeader {
+        title: Some(title.to_string()),
+        date: Some(Local::now().format(DATE_FORMAT).to_string()),
+        category,
+        draft: draft.then_some(true),
+        ..Default::default()
+    };
+
+    let content = format!("{}# {title}\n", format_header(&header)?);
+
+    fs::create_dir_all(posts_dir)?;
+    fs::write(&filepath, content)?;
+
+    Ok(filepath)
+}
+
+/// Escape `text` for use in XML and HTML content and attribute values
+///
+/// # Examples
+///
+/// ```
+/// use markhor::escape_xml;
+///
+/// assert_eq!(escape_xml(r#"<p class="x">Tom & Jerry's</p>"#), "&lt;p class=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/p&gt;");
+/// ```
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Turn `text` into a lowercase string that is safe to use in file names and URLs.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::slugify;
+///
+/// assert_eq!(slugify("My Post Title"), "my-post-title");
+/// assert_eq!(slugify("  Rust: 2022 edition!? "), "rust-2022-edition");
+/// assert_eq!(slugify("Über Grüße"), "über-grüße");
+/// assert_eq!(slugify("???"), "");
+/// ```
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_string()
+}
+
 pub fn copy_static_files<P>(static_dir: P, out_dir: P) -> Result<(), Box<dyn Error>>
 where
     P: AsRef<Path> + Copy,
@@ -273,3 +912,104 @@ where
 
     some_comp(a.as_ref().unwrap(), b.as_ref().unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::config::Config;
+    use crate::init::init_site;
+    use crate::test_utils::TempDir;
+    use crate::{check_site, clean_output, render_site};
+
+    #[test]
+    fn test_clean_output_should_keep_sources() {
+        let site_dir = TempDir::new("clean");
+        site_dir.write("posts/post.md", "# Post");
+        site_dir.write("out/nested/post.html", "<h1>Post</h1>");
+
+        let mut config = Config {
+            root: site_dir.to_path_buf(),
+            ..Default::default()
+        };
+
+        for output in [".", "..", "posts/.."] {
+            config.directories.output = PathBuf::from(output);
+            assert!(clean_output(&config).is_err(), "removed {output}");
+        }
+        assert!(site_dir.join("posts").is_dir());
+
+        config.directories.output = PathBuf::from("out");
+        clean_output(&config).unwrap();
+        assert!(!site_dir.join("out").exists());
+        assert!(site_dir.join("posts").is_dir());
+    }
+
+    #[test]
+    fn test_render_site_should_reject_duplicate_output_paths() {
+        let site_dir = TempDir::new("paths");
+        init_site(&site_dir).unwrap();
+        let config = Config::load(&site_dir).unwrap();
+        assert!(check_site(&config, false).is_ok());
+
+        site_dir.write("pages/index.md", "# Index");
+        site_dir.write("posts/about.md", "# About");
+
+        let error = check_site(&config, false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Found files that would be written more than once:\n  about.html (post about.md, page about.md)\n  index.html (page index.md)"
+        );
+    }
+
+    #[test]
+    fn test_render_site_should_report_all_broken_links() {
+        let site_dir = TempDir::new("broken");
+        init_site(&site_dir).unwrap();
+        let config = Config::load(&site_dir).unwrap();
+
+        site_dir.write("posts/draft.md", "---\ndraft = true\n---\n# Draft");
+        site_dir.write("posts/linking.md", "See [[draft]]");
+        site_dir.write("pages/links.md", "See [gone](@/gone.md)");
+
+        let error = check_site(&config, false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Found links to missing posts:\n  \
+             linking.md -> @/draft.md (a draft, build with --drafts to include it)\n  \
+             links.md -> @/gone.md"
+        );
+
+        fs::remove_file(site_dir.join("pages/links.md")).unwrap();
+        assert!(check_site(&config, true).is_ok());
+    }
+
+    #[test]
+    fn test_render_site_should_skip_missing_tag_and_series_templates() {
+        let site_dir = TempDir::new("optional");
+        init_site(&site_dir).unwrap();
+        let config = Config::load(&site_dir).unwrap();
+
+        site_dir.write(
+            "posts/tagged.md",
+            "---\ntags = [\"rust\"]\nseries = \"Intro\"\n---\n# Tagged",
+        );
+        for template in ["tag.html", "tags.html", "series.html"] {
+            fs::remove_file(site_dir.join("templates").join(template)).unwrap();
+        }
+
+        let files = render_site(&config, false).unwrap();
+        assert!(!files
+            .iter()
+            .any(|file| file.path.starts_with("tags/") || file.path.starts_with("series/")));
+
+        let sitemap = files
+            .iter()
+            .find(|file| file.path == "sitemap.xml")
+            .unwrap();
+        assert!(sitemap.content.contains("/tagged.html</loc>"));
+        assert!(!sitemap.content.contains("/tags/"));
+        assert!(!sitemap.content.contains("/series/"));
+    }
+}
@@ -0,0 +1,261 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use percent_encoding::percent_decode_str;
+
+use crate::codeblock::code_ranges;
+use crate::PostMeta;
+
+/// prefix of internal links, followed by the path of a post relative to the posts directory
+pub const INTERNAL_LINK_PREFIX: &str = "@/";
+
+/// A post that can be linked to
+#[derive(Debug, Clone)]
+struct LinkTarget {
+    url: String,
+    title: Option<String>,
+}
+
+/// All posts that can be linked to using [INTERNAL_LINK_PREFIX] or `[[wiki links]]`
+#[derive(Debug, Default)]
+pub struct LinkTargets {
+    /// keyed by [PostMeta::source_file]
+    by_source: HashMap<String, LinkTarget>,
+    /// source files keyed by their name without extension, for wiki links
+    by_name: HashMap<String, Vec<String>>,
+    /// source files of drafts that are not built, links to them are broken but reported as drafts
+    drafts: HashSet<String>,
+}
+
+/// `source_file` with `/` separators on every platform
+pub fn normalize(source_file: &str) -> String {
+    source_file.replace('\\', "/")
+}
+
+impl LinkTargets {
+    pub fn new<'a>(posts: impl IntoIterator<Item = &'a PostMeta>) -> Self {
+        let mut targets = LinkTargets::default();
+        for meta in posts {
+            targets.add(meta);
+        }
+        targets
+    }
+
+    pub fn add(&mut self, meta: &PostMeta) {
+        let source = normalize(&meta.source_file);
+        self.add_name(&source);
+
+        self.by_source.insert(
+            source,
+            LinkTarget {
+                url: format!("/{}", normalize(&meta.rendered_to)),
+                title: meta.header.as_ref().and_then(|h| h.title.clone()),
+            },
+        );
+    }
+
+    /// Add a draft that is not built, so links to it can be reported as links to a draft (see [LinkTargets::is_draft]).
+    ///
+    /// Wiki links resolve drafts like other posts, so they are unique in builds with and without drafts.
+    pub fn add_draft(&mut self, source_file: &str) {
+        let source = normalize(source_file);
+        self.add_name(&source);
+        self.drafts.insert(source);
+    }
+
+    fn add_name(&mut self, source: &str) {
+        if let Some(name) = Path::new(source).file_stem() {
+            self.by_name
+                .entry(name.to_string_lossy().to_string())
+                .or_default()
+                .push(source.to_string());
+        }
+    }
+
+    /// Whether an internal link points to a draft added with [LinkTargets::add_draft]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use markhor::links::LinkTargets;
+    ///
+    /// let mut targets = LinkTargets::default();
+    /// targets.add_draft("notes/draft.md");
+    ///
+    /// assert!(targets.is_draft("@/notes/draft.md#intro"));
+    /// assert!(!targets.is_draft("@/notes/other.md"));
+    /// assert_eq!(targets.expand_wiki_links("[[draft]]"), "[draft](<@/notes/draft.md>)");
+    /// ```
+    pub fn is_draft(&self, link: &str) -> bool {
+        split_link(link).is_some_and(|(path, _)| self.drafts.contains(path.as_ref()))
+    }
+
+    /// Resolve an internal link like `@/notes/post.md#section` to the url of the post,
+    /// keeping the fragment. Returns the linked source file and the url.
+    ///
+    /// The path may be percent-encoded, e.g. `@/my%20post.md`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use markhor::PostMeta;
+    /// use markhor::links::LinkTargets;
+    ///
+    /// let meta = PostMeta {
+    ///     source_file: "2022/post.md".to_string(),
+    ///     rendered_to: "notes/post.html".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let targets = LinkTargets::new([&meta]);
+    ///
+    /// assert_eq!(targets.resolve("@/2022/post.md"), Some(("2022/post.md", "/notes/post.html".to_string())));
+    /// assert_eq!(targets.resolve("@/2022/post.md#intro").unwrap().1, "/notes/post.html#intro");
+    /// assert_eq!(targets.resolve("@/missing.md"), None);
+    /// ```
+    pub fn resolve<'a>(&'a self, link: &str) -> Option<(&'a str, String)> {
+        let (path, fragment) = split_link(link)?;
+
+        let (source, target) = self.by_source.get_key_value(path.as_ref())?;
+        let url = match fragment {
+            Some(fragment) => format!("{}#{fragment}", target.url),
+            None => target.url.clone(),
+        };
+        Some((source, url))
+    }
+
+    /// Find the source file for a wiki link, by its path without extension or, if unique, its name
+    fn resolve_wiki(&self, name: &str) -> Option<&str> {
+        let with_extension = format!("{name}.md");
+        if let Some((source, _)) = self.by_source.get_key_value(&with_extension) {
+            return Some(source);
+        }
+        if let Some(source) = self.drafts.get(&with_extension) {
+            return Some(source);
+        }
+
+        match self.by_name.get(name).map(Vec::as_slice) {
+            Some([source]) => Some(source),
+            _ => None,
+        }
+    }
+
+    /// Replace `[[name]]` and `[[name|label]]` with markdown links using [INTERNAL_LINK_PREFIX].
+    ///
+    /// `name` is the path of a post without extension, or only its file name if that is unique.
+    /// The destination is enclosed in `<>`, so names may contain spaces.
+    /// Without label, the title of the post is used as link text.
+    /// Wiki links that cannot be resolved point to `@/name`, so they are reported as broken.
+    /// Wiki links in code (see [code_ranges]) are kept as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use markhor::{PostHeader, PostMeta};
+    /// use markhor::links::LinkTargets;
+    ///
+    /// let meta = PostMeta {
+    ///     source_file: "2022/other-post.md".to_string(),
+    ///     rendered_to: "notes/other-post.html".to_string(),
+    ///     header: Some(PostHeader {
+    ///         title: Some("Other post".to_string()),
+    ///         ..Default::default()
+    ///     }),
+    ///     ..Default::default()
+    /// };
+    /// let targets = LinkTargets::new([&meta]);
+    ///
+    /// assert_eq!(
+    ///     targets.expand_wiki_links("See [[other-post]] and [[2022/other-post|this]], not [[missing]]."),
+    ///     "See [Other post](<@/2022/other-post.md>) and [this](<@/2022/other-post.md>), not [missing](<@/missing>)."
+    /// );
+    /// assert_eq!(targets.expand_wiki_links("`[[other-post]]`"), "`[[other-post]]`");
+    /// ```
+    pub fn expand_wiki_links(&self, markdown: &str) -> String {
+        let code = code_ranges(markdown);
+
+        let mut expanded = String::with_capacity(markdown.len());
+        let mut copied = 0;
+        let mut pos = 0;
+
+        while let Some(start) = markdown[pos..].find("[[").map(|index| pos + index) {
+            if let Some(range) = code.iter().find(|range| range.contains(&start)) {
+                pos = range.end;
+                continue;
+            }
+            pos = start + 2;
+
+            let Some(length) = markdown[pos..].find("]]") else {
+                break;
+            };
+            let inner = &markdown[pos..pos + length];
+            if inner.is_empty() || inner.contains(['\n', '[', ']']) {
+                continue;
+            }
+
+            let (name, label) = match inner.split_once('|') {
+                Some((name, label)) => (name.trim(), Some(label.trim())),
+                None => (inner.trim(), None),
+            };
+
+            let link = match self.resolve_wiki(name) {
+                Some(source) => {
+                    let title = self
+                        .by_source
+                        .get(source)
+                        .and_then(|target| target.title.as_deref());
+                    let label = label.or(title).unwrap_or(name);
+                    format!("[{label}](<{INTERNAL_LINK_PREFIX}{source}>)")
+                }
+                None => format!(
+                    "[{}](<{INTERNAL_LINK_PREFIX}{name}>)",
+                    label.unwrap_or(name)
+                ),
+            };
+
+            expanded.push_str(&markdown[copied..start]);
+            expanded.push_str(&link);
+            pos += length + 2;
+            copied = pos;
+        }
+
+        expanded.push_str(&markdown[copied..]);
+        expanded
+    }
+}
+
+/// Path and fragment of an internal link, the path is percent-decoded
+fn split_link(link: &str) -> Option<(Cow<'_, str>, Option<&str>)> {
+    let path = link.strip_prefix(INTERNAL_LINK_PREFIX)?;
+    let (path, fragment) = match path.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (path, None),
+    };
+
+    let path = percent_decode_str(path)
+        .decode_utf8()
+        .unwrap_or(Cow::Borrowed(path));
+    Some((path, fragment))
+}
+
+/// A link to a missing post, see [LinkTargets::resolve]
+#[derive(Debug, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// source file of the post containing the link
+    pub source: String,
+    pub link: String,
+    /// the link points to a draft that is not built, see [LinkTargets::is_draft]
+    pub draft: bool,
+}
+
+/// Error listing every broken link of a build
+pub fn broken_links_error(broken_links: &[BrokenLink]) -> String {
+    let mut message = String::from("Found links to missing posts:");
+    for broken in broken_links {
+        message.push_str(&format!("\n  {} -> {}", broken.source, broken.link));
+        if broken.draft {
+            message.push_str(" (a draft, build with --drafts to include it)");
+        }
+    }
+    message
+}
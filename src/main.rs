@@ -1,31 +1,89 @@
-use std::path::Path;
+use std::fs;
+use std::path::PathBuf;
 use std::thread;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use log::info;
 use simple_logger::SimpleLogger;
 
+use markhor::config::Config;
+use markhor::highlight::theme_css;
+use markhor::init::init_site;
 use markhor::watch::watch_directories;
-use markhor::{copy_static_files, generate_site};
-
-const POSTS_DIR: &str = "posts";
-const OUT_DIR: &str = "out";
-const STATIC_DIR: &str = "static";
-const TEMPLATES_DIR: &str = "templates";
-const TEMPLATES_GLOB: &str = "templates/**/*";
+use markhor::{check_site, clean_output, copy_static_files, generate_site, new_post};
 
 #[derive(Parser, Debug)]
 #[clap(version)]
 struct Args {
-    /// Serve generated files
-    #[clap(long)]
-    serve: bool,
-    /// Watch source directories for changes and rebuild
-    #[clap(long)]
-    watch: bool,
-    /// Build drafts
-    #[clap(long)]
-    drafts: bool,
+    /// Site directory containing the sources and markhor.toml
+    #[clap(long, short, global = true, default_value = ".")]
+    source: PathBuf,
+    /// Output directory, overrides the one configured in markhor.toml
+    #[clap(long, short, global = true)]
+    output: Option<PathBuf>,
+    /// Defaults to build
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Build the site
+    Build {
+        /// Build drafts
+        #[clap(long)]
+        drafts: bool,
+    },
+    /// Build and serve the site, rebuilding on changes
+    Serve {
+        /// Build drafts
+        #[clap(long)]
+        drafts: bool,
+        /// Address to listen on
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        address: String,
+        /// Do not watch source directories for changes
+        #[clap(long)]
+        no_watch: bool,
+    },
+    /// Build the site and rebuild on changes
+    Watch {
+        /// Build drafts
+        #[clap(long)]
+        drafts: bool,
+    },
+    /// Render the site without writing any output
+    Check {
+        /// Check drafts
+        #[clap(long)]
+        drafts: bool,
+    },
+    /// Remove the output directory
+    Clean,
+    /// Create a new post in the posts directory
+    New {
+        /// Title of the post, also used for the file name
+        title: String,
+        /// Category of the post
+        #[clap(long)]
+        category: Option<String>,
+        /// Mark the post as draft
+        #[clap(long)]
+        draft: bool,
+    },
+    /// Write the stylesheet for code highlighted with `highlight_style = "classes"`
+    ThemeCss {
+        /// Theme to export, defaults to the configured `highlight_theme`
+        #[clap(long)]
+        theme: Option<String>,
+        /// File to write, defaults to highlight.css in the static directory
+        path: Option<PathBuf>,
+    },
+    /// Create a new site with example templates and a first post
+    Init {
+        /// Directory to create the site in, defaults to --source
+        path: Option<PathBuf>,
+    },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -35,49 +93,102 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_module_level("globset", log::LevelFilter::Error)
         .init()?;
 
-    let posts_dir = Path::new(POSTS_DIR);
-    let output_dir = Path::new(OUT_DIR);
+    if let Some(Command::Init { path }) = args.command {
+        let path = path.unwrap_or(args.source);
+        init_site(&path)?;
+        info!("Created new site in {}", path.display());
+        return Ok(());
+    }
 
-    generate_site(TEMPLATES_GLOB, posts_dir, output_dir, args.drafts)?;
+    let mut config = Config::load(&args.source)?;
+    if let Some(output) = args.output {
+        config.directories.output = std::env::current_dir()?.join(output);
+    }
 
-    copy_static_files(STATIC_DIR, OUT_DIR)?;
+    match args.command.unwrap_or(Command::Build { drafts: false }) {
+        Command::Build { drafts } => build(&config, drafts),
+        Command::Serve {
+            drafts,
+            address,
+            no_watch,
+        } => {
+            build(&config, drafts)?;
+            serve(config, drafts, address, !no_watch);
+            Ok(())
+        }
+        Command::Watch { drafts } => {
+            build(&config, drafts)?;
+            watch(config, drafts);
+            Ok(())
+        }
+        Command::Check { drafts } => {
+            let num_files = check_site(&config, drafts)?;
+            info!("Site is valid, {} files would be generated", num_files);
+            Ok(())
+        }
+        Command::Clean => clean_output(&config),
+        Command::New {
+            title,
+            category,
+            draft,
+        } => {
+            let path = new_post(config.posts_dir(), &title, category, draft)?;
+            info!("Created {}", path.display());
+            Ok(())
+        }
+        Command::ThemeCss { theme, path } => {
+            let theme = theme.unwrap_or_else(|| config.markdown.highlight_theme.clone());
+            let path = path.unwrap_or_else(|| config.static_dir().join("highlight.css"));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, theme_css(&theme)?)?;
+            info!("Wrote {} theme to {}", theme, path.display());
+            Ok(())
+        }
+        Command::Init { .. } => unreachable!("init is handled before loading the config"),
+    }
+}
 
-    let serve_handle = if args.serve {
-        Some(thread::spawn(move || {
-            markhor::serve::serve_files("127.0.0.1:8080", output_dir)
-                .expect("Failed to start fileserver");
-        }))
-    } else {
-        None
-    };
+fn build(config: &Config, drafts: bool) -> Result<(), Box<dyn std::error::Error>> {
+    generate_site(config, drafts)?;
 
-    let watch_handle = if args.watch {
-        info!("Watching files for changes...");
+    copy_static_files(&config.static_dir(), &config.output_dir())
+}
 
-        let change_listener = move |_| {
-            log::info!("Change detected, regenerating...");
-            if let Err(error) = generate_site(TEMPLATES_GLOB, POSTS_DIR, OUT_DIR, args.drafts) {
-                log::error!("Failed generating site: {}", error);
-            }
+fn serve(config: Config, drafts: bool, address: String, watch_files: bool) {
+    let output_dir = config.output_dir();
+    let serve_handle = thread::spawn(move || {
+        markhor::serve::serve_files(address, output_dir).expect("Failed to start fileserver");
+    });
 
-            if let Err(error) = copy_static_files(STATIC_DIR, OUT_DIR) {
-                log::error!("Failed to copy static assets: {}", error);
-            }
-        };
-
-        Some(thread::spawn(move || {
-            watch_directories(TEMPLATES_DIR, POSTS_DIR, STATIC_DIR, change_listener)
-                .expect("Failed to watch files");
-        }))
-    } else {
-        None
+    if watch_files {
+        watch(config, drafts);
+    }
+
+    serve_handle.join().expect("Failed to join serve thread");
+}
+
+fn watch(config: Config, drafts: bool) {
+    info!("Watching files for changes...");
+
+    let dirs = [
+        config.templates_dir(),
+        config.posts_dir(),
+        config.pages_dir(),
+        config.static_dir(),
+    ];
+
+    let change_listener = move |_| {
+        log::info!("Change detected, regenerating...");
+        if let Err(error) = generate_site(&config, drafts) {
+            log::error!("Failed generating site: {}", error);
+        }
+
+        if let Err(error) = copy_static_files(&config.static_dir(), &config.output_dir()) {
+            log::error!("Failed to copy static assets: {}", error);
+        }
     };
 
-    if let Some(handle) = serve_handle {
-        handle.join().expect("Failed to join serve thread");
-    }
-    if let Some(handle) = watch_handle {
-        handle.join().expect("Failed to join watch thread");
-    }
-    Ok(())
+    watch_directories(&dirs, change_listener).expect("Failed to watch files");
 }
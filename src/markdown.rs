@@ -1,15 +1,24 @@
 use log::info;
-use pulldown_cmark::{html, Parser, Tag};
+use pulldown_cmark::{html, CodeBlockKind, Event, Parser, Tag};
 
-use crate::{Post, PostHeader, PostMeta, TocHeading};
+use crate::codeblock::{render_code_block, CodeBlockOptions};
+use crate::config::MarkdownOptions;
+use crate::links::{BrokenLink, LinkTargets, INTERNAL_LINK_PREFIX};
+use crate::permalink::{check_slug, expand_permalink};
+use crate::shortcodes::{error_chain, render_document_shortcodes};
+use crate::{slugify, Post, PostHeader, PostMeta, TocHeading};
+use pulldown_cmark::Event::Code;
 use pulldown_cmark::Event::End;
 use pulldown_cmark::Event::Start;
 use pulldown_cmark::Event::Text;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
 use std::fs;
+use std::io;
 use std::ops::Add;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tera::Tera;
 
 pub const MARKDOWN_HEADER_DELIMITER: &str = "---\n";
 
@@ -17,36 +26,96 @@ pub struct ConvertedMarkdown {
     pub content: String,
     pub headings: Vec<TocHeading>,
     pub preview_text: String,
+    /// source files of all linked posts, see [LinkTargets::resolve]
+    pub links: Vec<String>,
+    /// internal links to missing posts
+    pub broken_links: Vec<String>,
 }
 
 /// Convert markdown to html
 /// Extracts the first paragraph as preview text.
+///
+/// Every heading gets a unique `id` (see [heading_id]), and with
+/// [`MarkdownOptions::heading_anchors`] a self-link anchor.
 /// # Examples
 /// ```
+/// use markhor::config::MarkdownOptions;
+/// use markhor::links::LinkTargets;
 /// use markhor::markdown::convert_markdown;
 ///
-/// let md = r"# Heading
-/// ### second";
-/// let converted = convert_markdown(md);
+/// let md = "# Heading\n### second\n## Heading";
+/// let converted = convert_markdown(md, &MarkdownOptions::default(), &LinkTargets::default());
 /// let headings = converted.headings;
 /// let html = converted.content;
 ///
-/// assert_eq!(html, "<h1>Heading</h1>\n<h2>second</h2>\n");
-/// assert_eq!(headings.len(), 2);
+/// assert_eq!(
+///     html,
+///     "<h1 id=\"heading\">Heading</h1>\n<h3 id=\"second\">second</h3>\n<h2 id=\"heading-1\">Heading</h2>\n"
+/// );
+/// assert_eq!(headings.len(), 3);
 /// assert_eq!(headings[0].level, 1);
 /// assert_eq!(headings[0].prev_level, None);
 /// assert_eq!(headings[0].text, "Heading");
+/// assert_eq!(headings[0].id, "heading");
 ///
-/// assert_eq!(headings[1].level, 2);
+/// assert_eq!(headings[1].level, 3);
 /// assert_eq!(headings[1].prev_level, Some(1));
 /// assert_eq!(headings[1].text, "second");
+///
+/// assert_eq!(headings[2].id, "heading-1");
+///
+/// let converted = convert_markdown("## Using `foo`", &MarkdownOptions::default(), &LinkTargets::default());
+/// assert_eq!(converted.headings[0].text, "Using foo");
+/// assert_eq!(converted.headings[0].id, "using-foo");
+///
+/// let options = MarkdownOptions {
+///     heading_anchors: true,
+///     ..Default::default()
+/// };
+/// let converted = convert_markdown("## Hi", &options, &LinkTargets::default());
+/// assert_eq!(
+///     converted.content,
+///     "<h2 id=\"hi\">Hi<a class=\"anchor\" href=\"#hi\" aria-hidden=\"true\">#</a></h2>\n"
+/// );
+/// ```
+pub fn convert_markdown(
+    markdown: &str,
+    options: &MarkdownOptions,
+    link_targets: &LinkTargets,
+) -> ConvertedMarkdown {
+    convert_markdown_with_ids(markdown, options, link_targets, &mut HashSet::new())
+}
+
+/// Like [convert_markdown], but heading ids are also unique among `used_ids`, which gets the new ids added.
+///
+/// # Examples
 /// ```
-pub fn convert_markdown(markdown: &str) -> ConvertedMarkdown {
-    let parser = Parser::new(markdown);
+/// use std::collections::HashSet;
+///
+/// use markhor::config::MarkdownOptions;
+/// use markhor::links::LinkTargets;
+/// use markhor::markdown::convert_markdown_with_ids;
+///
+/// let mut used_ids = HashSet::from(["intro".to_string()]);
+/// let converted = convert_markdown_with_ids("# Intro", &MarkdownOptions::default(), &LinkTargets::default(), &mut used_ids);
+///
+/// assert_eq!(converted.headings[0].id, "intro-1");
+/// assert!(used_ids.contains("intro-1"));
+/// ```
+pub fn convert_markdown_with_ids(
+    markdown: &str,
+    options: &MarkdownOptions,
+    link_targets: &LinkTargets,
+    used_ids: &mut HashSet<String>,
+) -> ConvertedMarkdown {
+    let markdown = link_targets.expand_wiki_links(markdown);
+    let parser = Parser::new(&markdown);
 
     let mut in_heading = false;
 
     let mut headings = Vec::<TocHeading>::new();
+    // ids of all headings in document order, including empty headings that are not in the toc
+    let mut heading_ids = Vec::<String>::new();
     // string to collect all the text inside a heading (if there are nested tags inside the h tags)
     // will keep the text, but loose the tags
     let mut current_heading = String::new();
@@ -62,14 +131,18 @@ pub fn convert_markdown(markdown: &str) -> ConvertedMarkdown {
 
     let mut first_paragraph = String::new();
 
-    let iterator = parser.map(|event| {
-        match &event {
+    let events: Vec<Event> = parser
+        .inspect(|event| match event {
             Start(Tag::Heading(_, _, _)) => {
                 in_heading = true;
                 current_heading = String::new();
             }
             End(Tag::Heading(level, _, _)) => {
                 in_heading = false;
+
+                let id = heading_id(&current_heading, used_ids);
+                heading_ids.push(id.clone());
+
                 if !current_heading.is_empty() {
                     let s = level.to_string();
                     let lvl_num = s
@@ -85,19 +158,19 @@ pub fn convert_markdown(markdown: &str) -> ConvertedMarkdown {
                         level: lvl_num,
                         prev_level,
                         text: current_heading.clone(),
+                        id,
                     };
                     headings.push(toc_entry);
                 }
             }
-            Start(Tag::Paragraph) => {
-                if preview_reading_state == PreviewReadingState::Searching {
-                    preview_reading_state = PreviewReadingState::Reading;
-                }
+            Start(Tag::Paragraph) if preview_reading_state == PreviewReadingState::Searching => {
+                preview_reading_state = PreviewReadingState::Reading;
             }
-            End(Tag::Paragraph) => {
-                if preview_reading_state == PreviewReadingState::Reading {
-                    preview_reading_state = PreviewReadingState::Complete;
-                }
+            End(Tag::Paragraph) if preview_reading_state == PreviewReadingState::Reading => {
+                preview_reading_state = PreviewReadingState::Complete;
+            }
+            Code(code) if in_heading => {
+                current_heading.push_str(code);
             }
             Text(text) => {
                 if in_heading {
@@ -108,21 +181,132 @@ pub fn convert_markdown(markdown: &str) -> ConvertedMarkdown {
                 }
             }
             _ => {}
+        })
+        .collect();
+
+    // second pass, now that the ids of all headings are known
+    let mut ids = heading_ids.iter();
+    let mut current_id = "";
+    let mut with_ids = Vec::with_capacity(events.len());
+    let mut links = Vec::<String>::new();
+    let mut broken_links = Vec::<String>::new();
+    // events of the fenced code block currently being read
+    let mut code_block: Option<Vec<Event>> = None;
+    for event in events {
+        if let Some(block) = code_block.as_mut() {
+            let is_end = matches!(event, End(Tag::CodeBlock(_)));
+            block.push(event);
+            if is_end {
+                let block = code_block.take().expect("code block is being read");
+                with_ids.extend(render_code_events(block, options));
+            }
+            continue;
         }
 
-        event
-    });
+        match event {
+            Start(Tag::Link(kind, destination, title))
+                if destination.starts_with(INTERNAL_LINK_PREFIX) =>
+            {
+                let destination = match link_targets.resolve(&destination) {
+                    Some((source, url)) => {
+                        if !links.iter().any(|link| link == source) {
+                            links.push(source.to_string());
+                        }
+                        url.into()
+                    }
+                    None => {
+                        broken_links.push(destination.to_string());
+                        destination
+                    }
+                };
+                with_ids.push(Start(Tag::Link(kind, destination, title)));
+            }
+            Start(Tag::CodeBlock(CodeBlockKind::Fenced(_))) => {
+                code_block = Some(vec![event]);
+            }
+            Start(Tag::Heading(level, _, classes)) => {
+                current_id = ids.next().expect("missing heading id");
+                with_ids.push(Start(Tag::Heading(level, Some(current_id), classes)));
+            }
+            End(Tag::Heading(..)) if options.heading_anchors => {
+                with_ids.push(Event::Html(
+                    format!(r##"<a class="anchor" href="#{current_id}" aria-hidden="true">#</a>"##)
+                        .into(),
+                ));
+                with_ids.push(event);
+            }
+            event => with_ids.push(event),
+        }
+    }
 
     let mut html_out = String::new();
-    html::push_html(&mut html_out, iterator);
+    html::push_html(&mut html_out, with_ids.into_iter());
 
     ConvertedMarkdown {
         content: html_out,
         headings,
         preview_text: first_paragraph,
+        links,
+        broken_links,
     }
 }
 
+/// Replace the events of a fenced code block with html rendered by [render_code_block],
+/// or keep them if there is nothing to change
+fn render_code_events<'a>(block: Vec<Event<'a>>, options: &MarkdownOptions) -> Vec<Event<'a>> {
+    let code_options = match block.first() {
+        Some(Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))) => CodeBlockOptions::parse(info),
+        _ => return block,
+    };
+
+    let code: String = block
+        .iter()
+        .filter_map(|event| match event {
+            Text(text) => Some(text.as_ref()),
+            _ => None,
+        })
+        .collect();
+
+    match render_code_block(&code, &code_options, options) {
+        Some(html) => vec![Event::Html(html.into())],
+        None => block,
+    }
+}
+
+/// Create an id for a heading with `text`, unique among the `used` ids.
+///
+/// The id is the slugified text, followed by a counter if it is already used.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashSet;
+/// use markhor::markdown::heading_id;
+///
+/// let mut used = HashSet::new();
+///
+/// assert_eq!(heading_id("Getting started", &mut used), "getting-started");
+/// assert_eq!(heading_id("Getting started", &mut used), "getting-started-1");
+/// assert_eq!(heading_id("Getting started", &mut used), "getting-started-2");
+/// assert_eq!(heading_id("???", &mut used), "heading");
+/// ```
+pub fn heading_id(text: &str, used: &mut HashSet<String>) -> String {
+    let mut slug = slugify(text);
+    if slug.is_empty() {
+        slug = "heading".to_string();
+    }
+
+    let mut id = slug.clone();
+    let mut counter = 0;
+    while used.contains(&id) {
+        counter += 1;
+        id = format!("{slug}-{counter}");
+    }
+
+    used.insert(id.clone());
+    id
+}
+
 /// Parse an optional [PostHeader] located at the start of a markdown document
 /// (beginning and ending with [MARKDOWN_HEADER_DELIMITER] )
 ///
@@ -168,86 +352,723 @@ pub fn split_md_and_header(input: &str) -> Result<(Option<PostHeader>, &str), to
     }
 }
 
-/// Convert every file in `posts_dir` to html, generates meta info and the html representation
+/// Format a [PostHeader] so it can be placed at the start of a markdown document,
+/// the inverse of [split_md_and_header]
+///
+/// # Examples
 ///
-/// Returns a [HashMap] of [Post]s, keyed by category
+/// ```
+/// use markhor::PostHeader;
+/// use markhor::markdown::{format_header, split_md_and_header};
+///
+/// let header = PostHeader {
+///     title: Some("mytitle".to_string()),
+///     draft: Some(true),
+///     ..Default::default()
+/// };
+///
+/// let formatted = format_header(&header).unwrap();
+/// assert_eq!(formatted, "---\ntitle = \"mytitle\"\ndraft = true\n---\n");
+///
+/// let (parsed, _) = split_md_and_header(&formatted).unwrap();
+/// assert_eq!(parsed, Some(header));
+/// ```
+pub fn format_header(header: &PostHeader) -> Result<String, toml::ser::Error> {
+    let header = toml::to_string(header)?;
+
+    Ok(format!(
+        "{MARKDOWN_HEADER_DELIMITER}{header}{MARKDOWN_HEADER_DELIMITER}"
+    ))
+}
+
+/// Recursively collect all markdown (`.md`) files in `dir`, relative to `dir` and sorted by path.
+///
+/// Hidden files and directories (starting with `.`) are skipped, as are other files like images
+/// stored next to a post.
+pub fn find_files(dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    fn visit(root: &Path, relative_dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(root.join(relative_dir))? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let relative_path = relative_dir.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                visit(root, &relative_path, files)?;
+            } else if relative_path.extension().is_some_and(|ext| ext == "md") {
+                files.push(relative_path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    visit(dir.as_ref(), Path::new(""), &mut files)?;
+    files.sort();
+
+    Ok(files)
+}
+
+/// Render the shortcodes of a post or page (see [render_document_shortcodes]) and convert it to html.
+///
+/// Links of shortcode bodies converted with the `markdown` filter are part of the result, their headings are not,
+/// but the ids of those headings are reserved first, so a heading of the document gets a suffix like `-1`
+/// when a shortcode body has a heading with the same text. Links to missing posts are added to `broken_links`.
+fn convert_document(
+    source_file: &str,
+    markdown: &str,
+    options: &MarkdownOptions,
+    tera: &mut Tera,
+    link_targets: &Arc<LinkTargets>,
+    broken_links: &mut Vec<BrokenLink>,
+) -> Result<ConvertedMarkdown, String> {
+    let (markdown, mut filtered) =
+        render_document_shortcodes(markdown, tera, options, link_targets).map_err(|e| {
+            format!(
+                "Failed to render shortcodes in {source_file}: {}",
+                error_chain(&e)
+            )
+        })?;
+
+    let mut converted =
+        convert_markdown_with_ids(&markdown, options, link_targets, &mut filtered.heading_ids);
+
+    for link in filtered.links {
+        if !converted.links.contains(&link) {
+            converted.links.push(link);
+        }
+    }
+    converted.broken_links.extend(filtered.broken_links);
+
+    broken_links.extend(converted.broken_links.iter().map(|link| BrokenLink {
+        source: source_file.to_string(),
+        link: link.clone(),
+        draft: link_targets.is_draft(link),
+    }));
+
+    Ok(converted)
+}
+
+/// Posts converted by [convert_posts]
+pub struct ConvertedPosts {
+    /// [Post]s keyed by category
+    pub posts: BTreeMap<Option<String>, Vec<Post>>,
+    /// all posts and skipped drafts, for resolving links from pages
+    pub link_targets: Arc<LinkTargets>,
+}
+
+/// Convert every file in `posts_dir` and its subdirectories to html, generates meta info and the html representation
+///
+/// The output name of a post is its `slug` header value or the file name.
+/// If a `permalink` pattern is given, it determines [PostMeta::rendered_to] (see [expand_permalink]).
+/// Otherwise the post is placed in a directory named after its category, and if `mirror_directories` is set,
+/// the subdirectory of the post is kept.
+///
+/// Shortcodes are rendered using `tera` before converting markdown, see [render_document_shortcodes].
+/// Links between posts are resolved (see [LinkTargets]), links to missing posts are added to `broken_links`.
 pub fn convert_posts(
     posts_dir: impl AsRef<Path>,
     render_drafts: bool,
-) -> Result<BTreeMap<Option<String>, Vec<Post>>, Box<dyn Error>> {
+    mirror_directories: bool,
+    permalink: Option<&str>,
+    markdown_options: &MarkdownOptions,
+    tera: &mut Tera,
+    broken_links: &mut Vec<BrokenLink>,
+) -> Result<ConvertedPosts, Box<dyn Error>> {
     let posts_dir = posts_dir.as_ref();
 
-    let mut posts = BTreeMap::<Option<String>, Vec<Post>>::new();
+    // first pass: read all posts, so their output paths are known when resolving links
+    let mut sources = Vec::new();
+    // drafts that are not rendered, so links to them can be reported as such
+    let mut drafts = Vec::new();
 
     info!("Using markdown files in {:?}", posts_dir);
-    for entry in fs::read_dir(posts_dir)? {
-        let name = entry?.file_name();
-        let mut filepath = PathBuf::from(posts_dir);
-        filepath.push(&name);
-
-        let out_name = match Path::new(&name).file_stem() {
-            Some(s) => s.to_os_string().to_string_lossy().to_string(),
-            None => name.to_string_lossy().to_string(),
-        };
-        let out_name = out_name.add(".html");
-
-        info!("Setting out_name for {:?} to {:?}", name, out_name);
+    for relative_path in find_files(posts_dir)? {
+        let filepath = posts_dir.join(&relative_path);
 
         let source = fs::read_to_string(&filepath)?;
 
         let (header, markdown) = split_md_and_header(&source)?;
 
-        let is_draft = header.as_ref().map_or(false, |h| h.draft.unwrap_or(false));
+        if let Some(header) = &header {
+            header
+                .check_dates()
+                .map_err(|e| format!("{}: {e}", relative_path.display()))?;
+        }
+
+        let is_draft = header.as_ref().is_some_and(PostHeader::is_draft);
 
         if !is_draft || render_drafts {
-            let mut category = None;
-            let mut out_path = if let Some(h) = &header {
-                if let Some(cat) = &h.category {
-                    info!("Post {} has category {}", filepath.display(), cat);
-                    category = Some(cat.to_string());
-                    PathBuf::from(cat)
-                } else {
-                    PathBuf::new()
+            let slug = match header.as_ref().and_then(|h| h.slug.as_ref()) {
+                Some(slug) => {
+                    check_slug(slug).map_err(|e| format!("{}: {e}", relative_path.display()))?;
+                    slug.to_string()
                 }
-            } else {
-                PathBuf::new()
+                None => match relative_path.file_stem() {
+                    Some(s) => s.to_string_lossy().to_string(),
+                    None => relative_path.to_string_lossy().to_string(),
+                },
             };
 
-            out_path.push(out_name);
+            let category = header.as_ref().and_then(|h| h.category.as_ref());
+            if let Some(cat) = category {
+                info!("Post {} has category {}", filepath.display(), cat);
+            }
 
-            let converted_md = convert_markdown(markdown);
+            let out_path = if let Some(pattern) = permalink {
+                PathBuf::from(expand_permalink(pattern, &slug, header.as_ref())?)
+            } else {
+                let mut out_path = category.map(PathBuf::from).unwrap_or_default();
+
+                if mirror_directories {
+                    if let Some(subdir) = relative_path.parent() {
+                        out_path.push(subdir);
+                    }
+                }
+                out_path.push(slug.add(".html"));
+                out_path
+            };
+
+            info!("Setting out_name for {:?} to {:?}", relative_path, out_path);
 
             let meta = PostMeta {
-                source_file: name.into_string().unwrap(),
+                source_file: relative_path.to_string_lossy().to_string(),
                 rendered_to: out_path.to_string_lossy().to_string(),
                 header,
-                preview_text: converted_md.preview_text,
+                preview_text: String::new(),
             };
+            let markdown = markdown.to_string();
 
-            let post = Post {
-                meta,
-                content: converted_md.content,
-                headings: converted_md.headings,
-            };
+            sources.push((meta, markdown));
+        } else {
+            drafts.push(relative_path.to_string_lossy().to_string());
+        }
+    }
 
-            match posts.get_mut(&category) {
-                Some(postvec) => {
-                    postvec.push(post);
-                }
-                None => {
-                    posts.insert(category, vec![post]);
-                }
+    let mut rendered_to = HashMap::new();
+    for (meta, _) in &sources {
+        if let Some(other) = rendered_to.insert(&meta.rendered_to, &meta.source_file) {
+            return Err(format!(
+                "Posts {} and {} are both rendered to {}",
+                other, meta.source_file, meta.rendered_to
+            )
+            .into());
+        }
+    }
+
+    let mut link_targets = LinkTargets::new(sources.iter().map(|(meta, _)| meta));
+    for draft in &drafts {
+        link_targets.add_draft(draft);
+    }
+    let link_targets = Arc::new(link_targets);
+
+    let mut posts = BTreeMap::<Option<String>, Vec<Post>>::new();
+
+    for (mut meta, markdown) in sources {
+        let converted_md = convert_document(
+            &meta.source_file,
+            &markdown,
+            markdown_options,
+            tera,
+            &link_targets,
+            broken_links,
+        )?;
+
+        meta.preview_text = converted_md.preview_text;
+
+        let category = meta.header.as_ref().and_then(|h| h.category.clone());
+        let post = Post {
+            meta,
+            content: converted_md.content,
+            headings: converted_md.headings,
+            links: converted_md.links,
+        };
+
+        match posts.get_mut(&category) {
+            Some(postvec) => {
+                postvec.push(post);
+            }
+            None => {
+                posts.insert(category, vec![post]);
             }
         }
     }
 
-    Ok(posts)
+    Ok(ConvertedPosts {
+        posts,
+        link_targets,
+    })
+}
+
+/// Convert every file in `pages_dir` and its subdirectories to html.
+///
+/// Unlike posts, pages are not grouped by category, their output path is their path relative to `pages_dir`.
+/// A missing `pages_dir` results in no pages. Pages can link to posts in `link_targets`,
+/// links to missing posts are added to `broken_links`.
+pub fn convert_pages(
+    pages_dir: impl AsRef<Path>,
+    render_drafts: bool,
+    markdown_options: &MarkdownOptions,
+    tera: &mut Tera,
+    link_targets: &Arc<LinkTargets>,
+    broken_links: &mut Vec<BrokenLink>,
+) -> Result<Vec<Post>, Box<dyn Error>> {
+    let pages_dir = pages_dir.as_ref();
+
+    if !pages_dir.is_dir() {
+        info!("No pages directory found, skipping");
+        return Ok(Vec::new());
+    }
+
+    let mut pages = Vec::new();
+
+    info!("Using pages in {:?}", pages_dir);
+    for relative_path in find_files(pages_dir)? {
+        let source = fs::read_to_string(pages_dir.join(&relative_path))?;
+
+        let (header, markdown) = split_md_and_header(&source)?;
+
+        if let Some(header) = &header {
+            header
+                .check_dates()
+                .map_err(|e| format!("{}: {e}", relative_path.display()))?;
+        }
+
+        let is_draft = header.as_ref().is_some_and(PostHeader::is_draft);
+        if is_draft && !render_drafts {
+            continue;
+        }
+
+        let converted_md = convert_document(
+            &relative_path.to_string_lossy(),
+            markdown,
+            markdown_options,
+            tera,
+            link_targets,
+            broken_links,
+        )?;
+
+        let meta = PostMeta {
+            source_file: relative_path.to_string_lossy().to_string(),
+            rendered_to: relative_path
+                .with_extension("html")
+                .to_string_lossy()
+                .to_string(),
+            header,
+            preview_text: converted_md.preview_text,
+        };
+
+        pages.push(Post {
+            meta,
+            content: converted_md.content,
+            headings: converted_md.headings,
+            links: converted_md.links,
+        });
+    }
+
+    Ok(pages)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use tera::Tera;
+
+    use crate::config::MarkdownOptions;
+    use crate::highlight::HighlightStyle;
+    use crate::links::{broken_links_error, LinkTargets};
+    use crate::markdown::{
+        convert_markdown, convert_pages, convert_posts, find_files, split_md_and_header,
+    };
+    use crate::test_utils::TempDir;
+    use crate::PostMeta;
+
+    fn nested_posts_dir() -> TempDir {
+        let dir = TempDir::new("posts");
+
+        dir.write("top.md", "# top");
+        dir.write(
+            "2022/renamed.md",
+            "---\nslug = \"custom\"\ndate = \"2022-03-05\"\n---\n# renamed",
+        );
+        dir.write(".git/HEAD", "ref");
+        dir.write("2022/03/image.png", [0x89, 0x50, 0x4e, 0x47, 0xff]);
+        dir.write(
+            "2022/03/nested.md",
+            "---\ncategory = \"notes\"\n---\n# nested",
+        );
+
+        dir
+    }
+
+    #[test]
+    fn test_convert_posts_should_find_nested_posts() {
+        let dir = nested_posts_dir();
+
+        assert_eq!(
+            find_files(&dir).unwrap(),
+            vec![
+                PathBuf::from("2022/03/nested.md"),
+                PathBuf::from("2022/renamed.md"),
+                PathBuf::from("top.md")
+            ]
+        );
+
+        let posts = convert_posts(
+            &dir,
+            false,
+            false,
+            None,
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &mut Vec::new(),
+        )
+        .unwrap()
+        .posts;
+        let nested = &posts[&Some("notes".to_string())][0].meta;
+        assert_eq!(nested.source_file, "2022/03/nested.md");
+        assert_eq!(nested.rendered_to, "notes/nested.html");
+        assert_eq!(posts[&None][0].meta.rendered_to, "custom.html");
+        assert_eq!(posts[&None][1].meta.rendered_to, "top.html");
+
+        let posts = convert_posts(
+            &dir,
+            false,
+            true,
+            None,
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &mut Vec::new(),
+        )
+        .unwrap()
+        .posts;
+        let nested = &posts[&Some("notes".to_string())][0].meta;
+        assert_eq!(nested.rendered_to, "notes/2022/03/nested.html");
+        assert_eq!(posts[&None][0].meta.rendered_to, "2022/custom.html");
+        assert_eq!(posts[&None][1].meta.rendered_to, "top.html");
+
+        // top.md has no date
+        assert!(convert_posts(
+            &dir,
+            false,
+            false,
+            Some("/{year}/{slug}/"),
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &mut Vec::new(),
+        )
+        .is_err());
+
+        let posts = convert_posts(
+            &dir,
+            false,
+            false,
+            Some("/{category}/{slug}/"),
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &mut Vec::new(),
+        )
+        .unwrap()
+        .posts;
+        assert_eq!(posts[&None][0].meta.rendered_to, "custom/index.html");
+        let nested = &posts[&Some("notes".to_string())][0].meta;
+        assert_eq!(nested.rendered_to, "notes/nested/index.html");
+    }
+
+    #[test]
+    fn test_convert_posts_should_reject_duplicate_output_paths() {
+        let dir = TempDir::new("duplicates");
+        dir.write("2022/intro.md", "# 2022");
+        dir.write("2023/intro.md", "# 2023");
+
+        let convert = |mirror_directories| {
+            convert_posts(
+                &dir,
+                false,
+                mirror_directories,
+                None,
+                &MarkdownOptions::default(),
+                &mut Tera::default(),
+                &mut Vec::new(),
+            )
+        };
+
+        let Err(error) = convert(false) else {
+            panic!("duplicate output paths should fail the build");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Posts 2022/intro.md and 2023/intro.md are both rendered to intro.html"
+        );
+        assert!(convert(true).is_ok());
+    }
+
+    #[test]
+    fn test_convert_posts_should_reject_invalid_dates() {
+        let dir = TempDir::new("dates");
+        dir.write(
+            "post.md",
+            "---\ndate = \"2022-03-05\"\nupdated = \"last week\"\n---\n# Post",
+        );
+
+        let Err(error) = convert_posts(
+            &dir,
+            false,
+            false,
+            None,
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &mut Vec::new(),
+        ) else {
+            panic!("an invalid updated date should fail the build");
+        };
+        assert!(error
+            .to_string()
+            .starts_with("post.md: failed to parse updated \"last week\""));
+    }
+
+    #[test]
+    fn test_convert_posts_should_resolve_links() {
+        let dir = TempDir::new("links");
+
+        dir.write(
+            "2022/first.md",
+            "---\ntitle = \"First\"\ncategory = \"notes\"\n---\nSee [[second]].",
+        );
+        dir.write(
+            "second.md",
+            "[back](@/2022/first.md#top) and [again](@/2022/first.md)\n\n```\n[[first]]\n```",
+        );
+
+        let posts = convert_posts(
+            &dir,
+            false,
+            false,
+            None,
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &mut Vec::new(),
+        )
+        .unwrap()
+        .posts;
+        let first = &posts[&Some("notes".to_string())][0];
+        assert!(first
+            .content
+            .contains(r#"<a href="/second.html">second</a>"#));
+        assert_eq!(first.links, vec!["second.md"]);
+        let second = &posts[&None][0];
+        assert!(second
+            .content
+            .contains(r#"<a href="/notes/first.html#top">back</a>"#));
+        assert!(second.content.contains("[[first]]"));
+        assert_eq!(second.links, vec!["2022/first.md"]);
+
+        dir.write("broken.md", "[[missing]], [gone](@/gone.md) and [[draft]]");
+        dir.write("draft.md", "---\ndraft = true\n---\n# Draft");
+        let mut broken_links = Vec::new();
+        convert_posts(
+            &dir,
+            false,
+            false,
+            None,
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &mut broken_links,
+        )
+        .unwrap();
+        assert_eq!(
+            broken_links_error(&broken_links),
+            "Found links to missing posts:\n  broken.md -> @/missing\n  broken.md -> @/gone.md\n  \
+             broken.md -> @/draft.md (a draft, build with --drafts to include it)"
+        );
+    }
 
-    use crate::markdown::split_md_and_header;
+    #[test]
+    fn test_convert_posts_should_resolve_links_with_spaces() {
+        let dir = TempDir::new("spaces");
+
+        dir.write("my post.md", "# Mine");
+        dir.write(
+            "linking.md",
+            "[[my post]], [encoded](@/my%20post.md) and [[no such post]]",
+        );
+
+        let mut broken_links = Vec::new();
+        let posts = convert_posts(
+            &dir,
+            false,
+            false,
+            None,
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &mut broken_links,
+        )
+        .unwrap()
+        .posts;
+
+        let linking = &posts[&None][0];
+        assert_eq!(
+            linking.content,
+            "<p><a href=\"/my%20post.html\">my post</a>, <a href=\"/my%20post.html\">encoded</a> \
+             and <a href=\"@/no%20such%20post\">no such post</a></p>\n"
+        );
+        assert_eq!(linking.links, vec!["my post.md"]);
+        assert_eq!(
+            broken_links_error(&broken_links),
+            "Found links to missing posts:\n  linking.md -> @/no such post"
+        );
+    }
+
+    #[test]
+    fn test_convert_pages_should_convert_shortcode_bodies() {
+        let dir = TempDir::new("shortcodes");
+        dir.write("page.md", "# Intro\n\n{% note() %}\n# Intro\n{% end %}");
+
+        let mut tera = Tera::default();
+        tera.autoescape_on(vec![]);
+        tera.add_raw_template("shortcodes/note.html", "{{ body | markdown }}")
+            .unwrap();
+        let options = MarkdownOptions {
+            heading_anchors: true,
+            ..Default::default()
+        };
+
+        let pages = convert_pages(
+            &dir,
+            false,
+            &options,
+            &mut tera,
+            &Arc::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+
+        // shortcode bodies are converted first, so the heading of the body keeps the plain id
+        assert_eq!(
+            pages[0].content,
+            "<h1 id=\"intro-1\">Intro<a class=\"anchor\" href=\"#intro-1\" aria-hidden=\"true\">#</a></h1>\n\
+             <h1 id=\"intro\">Intro<a class=\"anchor\" href=\"#intro\" aria-hidden=\"true\">#</a></h1>"
+        );
+    }
+
+    #[test]
+    fn test_convert_pages_should_keep_blank_lines_of_code_in_shortcode_bodies() {
+        let dir = TempDir::new("body-code");
+        dir.write("page.md", "{% note() %}\n```\na\n\nb\n```\n{% end %}");
+
+        let mut tera = Tera::default();
+        tera.autoescape_on(vec![]);
+        tera.add_raw_template(
+            "shortcodes/note.html",
+            "<div class=\"note\">{{ body | markdown }}</div>",
+        )
+        .unwrap();
+
+        let pages = convert_pages(
+            &dir,
+            false,
+            &MarkdownOptions::default(),
+            &mut tera,
+            &Arc::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            pages[0].content,
+            "<div class=\"note\"><pre><code>a\n&#10;b\n</code></pre>\n</div>"
+        );
+    }
+
+    #[test]
+    fn test_convert_pages_should_resolve_links_in_shortcode_bodies() {
+        let dir = TempDir::new("body-links");
+        dir.write(
+            "page.md",
+            "{% note() %}\nSee [[post]] and [this](@/missing.md)\n{% end %}",
+        );
+
+        let mut tera = Tera::default();
+        tera.autoescape_on(vec![]);
+        tera.add_raw_template("shortcodes/note.html", "{{ body | markdown }}")
+            .unwrap();
+        let post = PostMeta {
+            source_file: "post.md".to_string(),
+            rendered_to: "post.html".to_string(),
+            header: None,
+            preview_text: String::new(),
+        };
+        let link_targets = Arc::new(LinkTargets::new([&post]));
+
+        let mut broken_links = Vec::new();
+        let pages = convert_pages(
+            &dir,
+            false,
+            &MarkdownOptions::default(),
+            &mut tera,
+            &link_targets,
+            &mut broken_links,
+        )
+        .unwrap();
+
+        assert_eq!(
+            pages[0].content,
+            "<p>See <a href=\"/post.html\">post</a> and <a href=\"@/missing.md\">this</a></p>"
+        );
+        assert_eq!(pages[0].links, vec!["post.md"]);
+        assert_eq!(
+            broken_links_error(&broken_links),
+            "Found links to missing posts:\n  page.md -> @/missing.md"
+        );
+    }
+
+    #[test]
+    fn test_convert_pages_should_keep_relative_path() {
+        let dir = TempDir::new("pages");
+
+        dir.write("about.md", "---\ncategory = \"ignored\"\n---\n# About");
+        dir.write("legal/imprint.md", "# Imprint");
+        dir.write("legal/draft.md", "---\ndraft = true\n---\n# Draft");
+
+        let pages = convert_pages(
+            &dir,
+            false,
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &Arc::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        let rendered_to: Vec<&str> = pages.iter().map(|p| p.meta.rendered_to.as_str()).collect();
+        assert_eq!(rendered_to, vec!["about.html", "legal/imprint.html"]);
+
+        assert_eq!(
+            convert_pages(
+                &dir,
+                true,
+                &MarkdownOptions::default(),
+                &mut Tera::default(),
+                &Arc::default(),
+                &mut Vec::new(),
+            )
+            .unwrap()
+            .len(),
+            3
+        );
+        assert!(convert_pages(
+            dir.join("missing"),
+            false,
+            &MarkdownOptions::default(),
+            &mut Tera::default(),
+            &Arc::default(),
+            &mut Vec::new(),
+        )
+        .unwrap()
+        .is_empty());
+    }
 
     #[test]
     fn test_split_md_and_header_should_read_meta() {
@@ -270,6 +1091,26 @@ date = "2022-02-01"
         assert_eq!(content, "# heading")
     }
 
+    #[test]
+    fn test_split_md_and_header_should_keep_extra_values() {
+        let input = r##"---
+title = "mytitle"
+subtitle = "mysubtitle"
+hero_color = "#ff0000"
+reading_time = 5
+---
+# heading"##;
+
+        let (header, _) = split_md_and_header(input).unwrap();
+        let header = header.unwrap();
+
+        assert_eq!(header.title.unwrap(), "mytitle".to_string());
+        assert_eq!(header.extra.len(), 3);
+        assert_eq!(header.extra["subtitle"].as_str(), Some("mysubtitle"));
+        assert_eq!(header.extra["hero_color"].as_str(), Some("#ff0000"));
+        assert_eq!(header.extra["reading_time"].as_integer(), Some(5));
+    }
+
     #[test]
     fn test_split_md_and_header_should_handle_no_meta() {
         let input = r"# heading".to_string();
@@ -310,4 +1151,51 @@ title = "test"
 
         assert!(header.unwrap().draft.is_none());
     }
+
+    #[test]
+    fn test_convert_markdown_should_highlight_code_blocks() {
+        let options = MarkdownOptions {
+            highlight: true,
+            highlight_style: HighlightStyle::Classes,
+            ..Default::default()
+        };
+        let md = "```rust\n/* multi\nline */\nfn main() {}\n```\n\n```unknown\na < b\n```\n\n    indented\n";
+
+        let html = convert_markdown(md, &options, &Arc::default()).content;
+
+        assert!(html.starts_with(r#"<pre class="highlight hl-code"><code class="language-rust">"#));
+        // spans are closed at the end of every line and reopened on the next one
+        for line in html.lines().take(3) {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count()
+            );
+        }
+        assert!(html.lines().nth(1).unwrap().starts_with(
+            r#"<span class="hl-source hl-rust"><span class="hl-comment hl-block hl-rust">line "#
+        ));
+        // unknown languages and indented code blocks are not highlighted
+        assert!(html.contains("<pre><code class=\"language-unknown\">a &lt; b\n</code></pre>"));
+        assert!(html.contains("<pre><code>indented\n</code></pre>"));
+    }
+
+    #[test]
+    fn test_convert_markdown_should_apply_code_block_options() {
+        let options = MarkdownOptions {
+            highlight: true,
+            highlight_style: HighlightStyle::Classes,
+            ..Default::default()
+        };
+        let md = "```rust,linenos,hl_lines=2,title=src/main.rs\nfn main() {\n}\n```\n";
+
+        let html = convert_markdown(md, &options, &Arc::default()).content;
+        let lines: Vec<&str> = html.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with(
+            r#"<figure class="code-block"><figcaption>src/main.rs</figcaption><pre class="highlight hl-code"><code class="language-rust"><span class="line"><span class="line-number">1</span><span class="hl-source hl-rust">"#
+        ));
+        assert!(lines[1].starts_with(r#"<span class="line hl"><span class="line-number">2</span>"#));
+        assert_eq!(lines[2], "</code></pre></figure>");
+    }
 }
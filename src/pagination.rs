@@ -0,0 +1,100 @@
+use serde::Serialize;
+
+/// directory of the second and following pages, e.g. `page/2/index.html`
+pub const PAGE_DIR: &str = "page";
+
+/// One page of a paginated list, available as `paginator` in the index and category templates
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Paginator<'a, T> {
+    /// number of the current page, starting at 1
+    pub current: usize,
+    /// total number of pages
+    pub total: usize,
+    /// output path of the current page
+    pub path: String,
+    /// output path of the previous page
+    pub prev: Option<String>,
+    /// output path of the next page
+    pub next: Option<String>,
+    /// output path of the first page
+    pub first: String,
+    /// output path of the last page
+    pub last: String,
+    /// the items on this page
+    pub posts: &'a [T],
+}
+
+/// Split `items` into pages of `page_size` items, or a single page if `page_size` is `None`.
+///
+/// The first page is written to `first_page`, following pages to `<dir>/page/<n>/index.html`.
+/// There is always at least one (possibly empty) page.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::pagination::paginate;
+///
+/// let items = [1, 2, 3, 4, 5];
+/// let pages = paginate(&items, Some(2), "notes.html", "notes");
+///
+/// assert_eq!(pages.len(), 3);
+/// assert_eq!(pages[0].path, "notes.html");
+/// assert_eq!(pages[0].prev, None);
+/// assert_eq!(pages[0].next.as_deref(), Some("notes/page/2/index.html"));
+/// assert_eq!(pages[1].posts, &[3, 4]);
+/// assert_eq!(pages[2].current, 3);
+/// assert_eq!(pages[2].total, 3);
+/// assert_eq!(pages[2].posts, &[5]);
+/// assert_eq!(pages[2].prev.as_deref(), Some("notes/page/2/index.html"));
+/// assert_eq!(pages[2].last, "notes/page/3/index.html");
+///
+/// let pages = paginate(&items, None, "index.html", "");
+/// assert_eq!(pages.len(), 1);
+/// assert_eq!(pages[0].posts.len(), 5);
+///
+/// let pages = paginate(&items, Some(2), "index.html", "");
+/// assert_eq!(pages[1].path, "page/2/index.html");
+/// ```
+pub fn paginate<'a, T>(
+    items: &'a [T],
+    page_size: Option<usize>,
+    first_page: &str,
+    dir: &str,
+) -> Vec<Paginator<'a, T>> {
+    let page_size = page_size.unwrap_or(items.len()).max(1);
+
+    let chunks: Vec<&[T]> = if items.is_empty() {
+        vec![items]
+    } else {
+        items.chunks(page_size).collect()
+    };
+    let total = chunks.len();
+
+    let page_path = |number: usize| {
+        if number == 1 {
+            first_page.to_string()
+        } else if dir.is_empty() {
+            format!("{PAGE_DIR}/{number}/index.html")
+        } else {
+            format!("{dir}/{PAGE_DIR}/{number}/index.html")
+        }
+    };
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, posts)| {
+            let current = index + 1;
+            Paginator {
+                current,
+                total,
+                path: page_path(current),
+                prev: (current > 1).then(|| page_path(current - 1)),
+                next: (current < total).then(|| page_path(current + 1)),
+                first: page_path(1),
+                last: page_path(total),
+                posts,
+            }
+        })
+        .collect()
+}
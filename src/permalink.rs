@@ -0,0 +1,104 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::{PostHeader, DATE_FORMAT};
+
+/// Check a `slug` from a post header, so it can not place the post outside of its directory
+///
+/// # Examples
+///
+/// ```
+/// use markhor::permalink::check_slug;
+///
+/// assert!(check_slug("hello-world").is_ok());
+/// assert!(check_slug("../../etc/x").is_err());
+/// assert!(check_slug("/absolute").is_err());
+/// assert!(check_slug("back\\slash").is_err());
+/// assert!(check_slug("").is_err());
+/// ```
+pub fn check_slug(slug: &str) -> Result<(), String> {
+    if slug.is_empty() || slug.contains(['/', '\\']) || slug.contains("..") {
+        return Err(format!(
+            "slug {slug:?} must not be empty or contain \"/\", \"\\\" or \"..\""
+        ));
+    }
+    Ok(())
+}
+
+/// Build the output path of a post from a permalink `pattern`, see [`crate::config::Config::permalink`]
+///
+/// Supported placeholders are `{slug}`, `{category}`, `{year}`, `{month}` and `{day}`.
+/// Empty path segments (e.g. from a post without category) are removed. A pattern ending in `/`
+/// renders the post into an `index.html` in that directory, a pattern without file extension gets `.html` appended.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::PostHeader;
+/// use markhor::permalink::expand_permalink;
+///
+/// let header = PostHeader {
+///     date: Some("2022-03-05".to_string()),
+///     category: Some("notes".to_string()),
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(
+///     expand_permalink("/{year}/{month}/{slug}/", "hello", Some(&header)).unwrap(),
+///     "2022/03/hello/index.html"
+/// );
+/// assert_eq!(
+///     expand_permalink("/{category}/{year}-{month}-{day}-{slug}", "hello", Some(&header)).unwrap(),
+///     "notes/2022-03-05-hello.html"
+/// );
+/// assert_eq!(
+///     expand_permalink("/{category}/{slug}.htm", "hello", None).unwrap(),
+///     "hello.htm"
+/// );
+/// assert!(expand_permalink("/{year}/{slug}/", "hello", None).is_err());
+/// ```
+pub fn expand_permalink(
+    pattern: &str,
+    slug: &str,
+    header: Option<&PostHeader>,
+) -> Result<String, String> {
+    let category = header.and_then(|h| h.category.as_deref()).unwrap_or("");
+
+    let mut path = pattern
+        .replace("{slug}", slug)
+        .replace("{category}", category);
+
+    if ["{year}", "{month}", "{day}"]
+        .iter()
+        .any(|placeholder| path.contains(placeholder))
+    {
+        let date = header.and_then(|h| h.date.as_ref()).ok_or_else(|| {
+            format!("permalink {pattern:?} requires a date, but {slug:?} has none")
+        })?;
+        let date = NaiveDate::parse_from_str(date, DATE_FORMAT)
+            .map_err(|e| format!("failed to parse date {date:?} of {slug:?}: {e}"))?;
+
+        path = path
+            .replace("{year}", &format!("{:04}", date.year()))
+            .replace("{month}", &format!("{:02}", date.month()))
+            .replace("{day}", &format!("{:02}", date.day()));
+    }
+
+    let is_dir = path.ends_with('/');
+
+    let mut path = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    if is_dir {
+        if !path.is_empty() {
+            path.push('/');
+        }
+        path.push_str("index.html");
+    } else if !path.rsplit('/').next().unwrap_or("").contains('.') {
+        path.push_str(".html");
+    }
+
+    Ok(path)
+}
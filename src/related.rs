@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::search::{strip_html, tokenize};
+use crate::{Post, PostMeta};
+
+/// How much each kind of similarity contributes to the relatedness of two posts
+pub mod weights {
+    /// both posts have the same category
+    pub const CATEGORY: f64 = 1.0;
+    /// per tag both posts have
+    pub const TAG: f64 = 1.0;
+    /// multiplied with the cosine similarity (0 to 1) of the posts' tf-idf vectors
+    pub const TEXT: f64 = 2.0;
+}
+
+/// normalized tf-idf weight of every term of a post
+type TermVector = HashMap<String, f64>;
+
+/// Finds related posts based on shared category and tags and the similarity of their text
+pub struct RelatedPosts<'a> {
+    candidates: Vec<(&'a Post, TermVector)>,
+    /// number of candidates containing a term
+    document_frequency: HashMap<String, usize>,
+    num_documents: usize,
+    count: usize,
+}
+
+fn term_counts(post: &Post) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for token in tokenize(&strip_html(&post.content)) {
+        *counts.entry(token).or_default() += 1;
+    }
+    counts
+}
+
+fn category(post: &Post) -> Option<&String> {
+    post.meta.header.as_ref().and_then(|h| h.category.as_ref())
+}
+
+fn tags(post: &Post) -> HashSet<&String> {
+    post.meta
+        .header
+        .as_ref()
+        .and_then(|h| h.tags.as_ref())
+        .map(|tags| tags.iter().collect())
+        .unwrap_or_default()
+}
+
+fn cosine_similarity(a: &TermVector, b: &TermVector) -> f64 {
+    a.iter()
+        .filter_map(|(term, weight)| b.get(term).map(|other| weight * other))
+        .sum()
+}
+
+impl<'a> RelatedPosts<'a> {
+    /// Prepare finding up to `count` related posts out of `candidates`.
+    ///
+    /// `candidates` are expected to be sorted newest first, equally related posts keep this order.
+    pub fn new(candidates: &[&'a Post], count: usize) -> Self {
+        let counts: Vec<HashMap<String, usize>> =
+            candidates.iter().map(|post| term_counts(post)).collect();
+
+        let mut document_frequency: HashMap<String, usize> = HashMap::new();
+        for term in counts.iter().flat_map(HashMap::keys) {
+            *document_frequency.entry(term.clone()).or_default() += 1;
+        }
+
+        let mut related = RelatedPosts {
+            candidates: Vec::new(),
+            document_frequency,
+            num_documents: candidates.len(),
+            count,
+        };
+        related.candidates = candidates
+            .iter()
+            .zip(counts)
+            .map(|(post, counts)| (*post, related.term_vector(counts)))
+            .collect();
+
+        related
+    }
+
+    fn term_vector(&self, counts: HashMap<String, usize>) -> TermVector {
+        let num_documents = self.num_documents as f64;
+
+        let mut vector: TermVector = counts
+            .into_iter()
+            .map(|(term, count)| {
+                let frequency = self.document_frequency.get(&term).copied().unwrap_or(0) as f64;
+                // smoothed inverse document frequency, terms in every post still count a little
+                let idf = ((1.0 + num_documents) / (1.0 + frequency)).ln() + 1.0;
+                (term, count as f64 * idf)
+            })
+            .collect();
+
+        let norm = vector
+            .values()
+            .map(|weight| weight * weight)
+            .sum::<f64>()
+            .sqrt();
+        if norm > 0.0 {
+            vector.values_mut().for_each(|weight| *weight /= norm);
+        }
+        vector
+    }
+
+    /// Up to `count` candidates most related to `post`, most related first.
+    ///
+    /// Candidates sharing neither category, tags nor any term with `post` are never related.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use markhor::{Post, PostHeader, PostMeta};
+    /// use markhor::related::RelatedPosts;
+    ///
+    /// let post = |name: &str, category: &str, content: &str| Post {
+    ///     meta: PostMeta {
+    ///         source_file: format!("{name}.md"),
+    ///         rendered_to: format!("{name}.html"),
+    ///         header: Some(PostHeader {
+    ///             category: Some(category.to_string()),
+    ///             ..Default::default()
+    ///         }),
+    ///         ..Default::default()
+    ///     },
+    ///     content: content.to_string(),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let rust = post("rust", "code", "<p>borrow checker lifetimes</p>");
+    /// let lifetimes = post("lifetimes", "notes", "<p>lifetimes explained</p>");
+    /// let cooking = post("cooking", "food", "<p>pasta recipe</p>");
+    /// let python = post("python", "code", "<p>dynamic typing</p>");
+    ///
+    /// let related = RelatedPosts::new(&[&rust, &lifetimes, &cooking, &python], 5);
+    /// let related_to_rust: Vec<&str> = related
+    ///     .related(&rust)
+    ///     .iter()
+    ///     .map(|meta| meta.source_file.as_str())
+    ///     .collect();
+    ///
+    /// assert_eq!(related_to_rust, vec!["python.md", "lifetimes.md"]);
+    /// ```
+    pub fn related(&self, post: &Post) -> Vec<&'a PostMeta> {
+        let vector = self.term_vector(term_counts(post));
+        let post_category = category(post);
+        let post_tags = tags(post);
+
+        let mut scored: Vec<(f64, &'a PostMeta)> = self
+            .candidates
+            .iter()
+            .filter(|(candidate, _)| candidate.meta.rendered_to != post.meta.rendered_to)
+            .map(|(candidate, candidate_vector)| {
+                let mut score = weights::TEXT * cosine_similarity(&vector, candidate_vector);
+                if post_category.is_some() && category(candidate) == post_category {
+                    score += weights::CATEGORY;
+                }
+                score += weights::TAG * post_tags.intersection(&tags(candidate)).count() as f64;
+
+                (score, &candidate.meta)
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+
+        // stable sort, equally related posts stay newest first
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        scored
+            .into_iter()
+            .take(self.count)
+            .map(|(_, meta)| meta)
+            .collect()
+    }
+}
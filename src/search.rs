@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+
+use crate::{OutputFile, Post};
+
+/// file containing the search index, relative to the output directory
+pub const SEARCH_INDEX: &str = "search_index.json";
+
+/// A post in the search index
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct SearchDocument<'a> {
+    pub title: Option<&'a str>,
+    /// absolute path of the post, e.g. `/notes/post.html`
+    pub url: String,
+    pub category: Option<&'a str>,
+    pub headings: Vec<&'a str>,
+    /// lowercase words of the post content, each word only once.
+    /// Left out in the inverted index, see [InvertedIndex::terms]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<Vec<String>>,
+}
+
+/// Search index with precomputed term lookup, so the browser does not have to tokenize every post
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct InvertedIndex<'a> {
+    pub documents: Vec<SearchDocument<'a>>,
+    /// every token, mapped to `[document index, number of occurrences]` pairs
+    pub terms: BTreeMap<String, Vec<(usize, usize)>>,
+}
+
+/// Remove html tags from `html` and decode the entities written by pulldown-cmark
+///
+/// # Examples
+///
+/// ```
+/// use markhor::search::strip_html;
+///
+/// assert_eq!(strip_html("<p>Fish &amp; <em>chips</em></p>"), "Fish & chips");
+/// ```
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Split `text` into lowercase words, ignoring punctuation and single characters
+///
+/// # Examples
+///
+/// ```
+/// use markhor::search::tokenize;
+///
+/// assert_eq!(tokenize("Hello, World! A test."), vec!["hello", "world", "test"]);
+/// ```
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// All tokens of a post: its title, headings and text content
+fn post_tokens(post: &Post) -> Vec<String> {
+    let title = post
+        .meta
+        .header
+        .as_ref()
+        .and_then(|h| h.title.as_deref())
+        .unwrap_or_default();
+
+    let mut tokens = tokenize(title);
+    tokens.extend(tokenize(&strip_html(&post.content)));
+    tokens
+}
+
+fn search_document<'a>(post: &'a Post, tokens: Option<Vec<String>>) -> SearchDocument<'a> {
+    let header = post.meta.header.as_ref();
+
+    SearchDocument {
+        title: header.and_then(|h| h.title.as_deref()),
+        url: format!("/{}", post.meta.rendered_to),
+        category: header.and_then(|h| h.category.as_deref()),
+        headings: post.headings.iter().map(|h| h.text.as_str()).collect(),
+        tokens,
+    }
+}
+
+/// Render a compact JSON search index of `posts` into [SEARCH_INDEX]
+///
+/// Without `inverted`, the index is a list of [SearchDocument]s containing their tokens,
+/// otherwise an [InvertedIndex].
+///
+/// # Examples
+///
+/// ```
+/// use markhor::{Post, PostMeta};
+/// use markhor::search::render_search_index;
+///
+/// let post = Post {
+///     meta: PostMeta {
+///         source_file: "post.md".to_string(),
+///         rendered_to: "notes/post.html".to_string(),
+///         header: None,
+///         preview_text: String::new(),
+///     },
+///     content: "<p>Rust rocks, rust!</p>".to_string(),
+///     headings: Vec::new(),
+///     links: Vec::new(),
+/// };
+///
+/// let index = render_search_index(&[&post], false).unwrap();
+/// assert_eq!(index.path, "search_index.json");
+/// assert!(index.content.contains(r#""url":"/notes/post.html""#));
+/// assert!(index.content.contains(r#""tokens":["rust","rocks"]"#));
+///
+/// let index = render_search_index(&[&post], true).unwrap();
+/// assert!(index.content.contains(r#""terms":{"rocks":[[0,1]],"rust":[[0,2]]}"#));
+/// ```
+pub fn render_search_index(
+    posts: &[&Post],
+    inverted: bool,
+) -> Result<OutputFile, serde_json::Error> {
+    let content = if inverted {
+        let mut documents = Vec::with_capacity(posts.len());
+        let mut terms: BTreeMap<String, Vec<(usize, usize)>> = BTreeMap::new();
+
+        for (id, post) in posts.iter().enumerate() {
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for token in post_tokens(post) {
+                *counts.entry(token).or_default() += 1;
+            }
+            for (token, count) in counts {
+                terms.entry(token).or_default().push((id, count));
+            }
+
+            documents.push(search_document(post, None));
+        }
+
+        serde_json::to_string(&InvertedIndex { documents, terms })?
+    } else {
+        let documents: Vec<SearchDocument> = posts
+            .iter()
+            .map(|post| {
+                let mut tokens = post_tokens(post);
+                let mut seen = HashSet::new();
+                tokens.retain(|token| seen.insert(token.clone()));
+
+                search_document(post, Some(tokens))
+            })
+            .collect();
+
+        serde_json::to_string(&documents)?
+    };
+
+    Ok(OutputFile {
+        path: SEARCH_INDEX.to_string(),
+        content,
+    })
+}
@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::{slugify, PostMeta};
+
+/// directory containing the series overview pages, relative to the output directory
+pub const SERIES_DIR: &str = "series";
+
+/// Posts sharing the same [`crate::PostHeader::series`]
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Series<'a> {
+    pub name: String,
+    pub slug: String,
+    /// path of the series overview page, relative to the output directory
+    pub rendered_to: String,
+    /// number of parts
+    pub count: usize,
+    /// all parts, in reading order
+    pub parts: Vec<&'a PostMeta>,
+}
+
+impl Series<'_> {
+    /// Position of `post` in [Series::parts], starting at 1
+    pub fn position(&self, post: &PostMeta) -> Option<usize> {
+        self.parts
+            .iter()
+            .position(|part| part.rendered_to == post.rendered_to)
+            .map(|index| index + 1)
+    }
+}
+
+/// Group posts by their series.
+///
+/// Parts are ordered by their `series_order`, parts without one come last.
+/// Otherwise, the order of `posts` (expected newest first) is reversed, so older parts come first.
+///
+/// Fails if a series has no usable [slugify]d name, or if two series share the same slug,
+/// as their pages would overwrite each other.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::{PostHeader, PostMeta};
+/// use markhor::series::collect_series;
+///
+/// let post = |name: &str, series_order: Option<u32>| PostMeta {
+///     source_file: format!("{name}.md"),
+///     rendered_to: format!("{name}.html"),
+///     header: Some(PostHeader {
+///         series: Some("Rust Tutorial".to_string()),
+///         series_order,
+///         ..Default::default()
+///     }),
+///     preview_text: String::new(),
+/// };
+///
+/// let (newest, intro, oldest) = (post("newest", None), post("intro", Some(1)), post("oldest", None));
+///
+/// let series = collect_series(&[&newest, &intro, &oldest]).unwrap();
+///
+/// assert_eq!(series.len(), 1);
+/// assert_eq!(series[0].name, "Rust Tutorial");
+/// assert_eq!(series[0].rendered_to, "series/rust-tutorial.html");
+/// assert_eq!(series[0].count, 3);
+/// assert_eq!(series[0].parts, vec![&intro, &oldest, &newest]);
+/// assert_eq!(series[0].position(&oldest), Some(2));
+///
+/// let mut other = post("other", None);
+/// other.header.as_mut().unwrap().series = Some("rust tutorial".to_string());
+/// assert_eq!(
+///     collect_series(&[&intro, &other]).unwrap_err(),
+///     "Series \"Rust Tutorial\" and \"rust tutorial\" would both be rendered to series/rust-tutorial.html"
+/// );
+/// ```
+pub fn collect_series<'a>(posts: &[&'a PostMeta]) -> Result<Vec<Series<'a>>, String> {
+    let mut posts_by_series = BTreeMap::<&str, Vec<&PostMeta>>::new();
+
+    for post in posts.iter().rev() {
+        if let Some(series) = post.header.as_ref().and_then(|h| h.series.as_ref()) {
+            posts_by_series.entry(series).or_default().push(post);
+        }
+    }
+
+    let mut names_by_slug = HashMap::new();
+    let mut series = Vec::with_capacity(posts_by_series.len());
+
+    for (name, mut parts) in posts_by_series {
+        let slug = slugify(name);
+        let rendered_to = format!("{SERIES_DIR}/{slug}.html");
+        if slug.is_empty() {
+            return Err(format!("Series {name:?} can not be used in a file name"));
+        }
+        if let Some(other) = names_by_slug.insert(slug.clone(), name) {
+            return Err(format!(
+                "Series {other:?} and {name:?} would both be rendered to {rendered_to}"
+            ));
+        }
+
+        // stable, parts without order keep their chronological order
+        parts.sort_by_key(|part| {
+            let order = part.header.as_ref().and_then(|h| h.series_order);
+            (order.is_none(), order)
+        });
+
+        series.push(Series {
+            name: name.to_string(),
+            rendered_to,
+            slug,
+            count: parts.len(),
+            parts,
+        });
+    }
+
+    Ok(series)
+}
@@ -3,7 +3,7 @@ use std::{
     fs,
     net::{TcpListener, TcpStream, ToSocketAddrs},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::Arc,
 };
 
 use log::error;
@@ -13,7 +13,7 @@ use rayon::ThreadPoolBuilder;
 use std::io::Read;
 use std::io::Write;
 
-pub fn serve_files<A>(address: A, dir: &'static Path) -> Result<(), Box<dyn Error>>
+pub fn serve_files<A>(address: A, dir: PathBuf) -> Result<(), Box<dyn Error>>
 where
     A: ToSocketAddrs,
 {
@@ -23,14 +23,13 @@ where
     info!("Running at http://{:#?}", listener.local_addr()?);
     info!("Serving directory '{}'", dir.display());
 
-    let dir = Arc::new(Mutex::new(dir));
+    let dir = Arc::new(dir);
     for stream in listener.incoming() {
         let dir = dir.clone();
         match stream {
             Ok(stream) => {
                 thread_pool.spawn(move || {
-                    let dir = dir.lock().unwrap();
-                    if let Err(error) = handle_conn(stream, *dir) {
+                    if let Err(error) = handle_conn(stream, dir.as_path()) {
                         error!("Error while handling request: {}", error);
                     }
                 });
@@ -75,6 +74,10 @@ where
 
     let mut pathbuf = PathBuf::from(dir.as_ref());
     pathbuf.push(path);
+    // directories from permalinks like /2022/03/post/
+    if pathbuf.is_dir() {
+        pathbuf.push("index.html");
+    }
     let path = pathbuf.as_path();
 
     if !path.is_file() {
@@ -0,0 +1,355 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use serde_json::{Map, Number, Value};
+use tera::{Context, Tera};
+
+use crate::codeblock::code_ranges;
+use crate::config::MarkdownOptions;
+use crate::links::LinkTargets;
+use crate::templating::{filters, has_template, FilterOutput, MarkdownFilter};
+
+/// directory inside the templates directory containing the shortcode templates
+pub const SHORTCODES_DIR: &str = "shortcodes";
+
+/// name of the context value containing the body of a block shortcode
+pub const BODY: &str = "body";
+
+/// A shortcode invocation: `name(key="value", n=1)`
+#[derive(Debug, PartialEq)]
+struct Call<'a> {
+    name: &'a str,
+    args: Map<String, Value>,
+}
+
+/// Simple cursor over the arguments of a shortcode
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> Option<()> {
+        self.skip_whitespace();
+        self.rest()
+            .starts_with(token)
+            .then(|| self.pos += token.len())
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_numeric()) {
+            return None;
+        }
+        self.pos += len;
+        Some(&rest[..len])
+    }
+
+    fn string(&mut self, quote: char) -> Option<Value> {
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => value.push(chars.next()?.1),
+                c if c == quote => {
+                    self.pos += index + 1;
+                    return Some(Value::String(value));
+                }
+                c => value.push(c),
+            }
+        }
+        None
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        let rest = self.rest();
+
+        if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            return self.string(quote);
+        }
+
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '.' || c == '-'))
+            .unwrap_or(rest.len());
+        let literal = &rest[..len];
+        let value = match literal {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => match literal.parse::<i64>() {
+                Ok(int) => Value::from(int),
+                Err(_) => Value::Number(Number::from_f64(literal.parse().ok()?)?),
+            },
+        };
+        self.pos += len;
+        Some(value)
+    }
+
+    /// `name(key=value, ...)`
+    fn call(&mut self) -> Option<Call<'a>> {
+        let name = self.ident()?;
+        self.eat("(")?;
+
+        let mut args = Map::new();
+        while self.eat(")").is_none() {
+            if !args.is_empty() {
+                self.eat(",")?;
+            }
+            let key = self.ident()?;
+            self.eat("=")?;
+            args.insert(key.to_string(), self.value()?);
+        }
+
+        Some(Call { name, args })
+    }
+}
+
+/// Find the `{% end %}` closing a block shortcode, returns its start and end
+fn find_end(text: &str) -> Option<(usize, usize)> {
+    let mut from = 0;
+    while let Some(start) = text[from..].find("{%").map(|index| from + index) {
+        let mut parser = Parser {
+            input: text,
+            pos: start + 2,
+        };
+        if parser.ident() == Some("end") && parser.eat("%}").is_some() {
+            return Some((start, parser.pos));
+        }
+        from = start + 2;
+    }
+    None
+}
+
+fn render_call(tera: &Tera, call: &Call, body: Option<&str>) -> Result<String, tera::Error> {
+    let mut context = Context::from_value(Value::Object(call.args.clone()))?;
+    if let Some(body) = body {
+        context.insert(BODY, body);
+    }
+
+    let html = tera.render(&format!("{SHORTCODES_DIR}/{}.html", call.name), &context)?;
+
+    Ok(remove_blank_lines(&html))
+}
+
+/// Remove blank lines, as they would end the html block and turn the rest into markdown.
+///
+/// Inside `<pre>` the line break of a blank line is kept as `&#10;`, so code keeps its blank lines.
+fn remove_blank_lines(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut open_pre = 0usize;
+
+    for line in html.lines() {
+        if line.trim().is_empty() {
+            if open_pre > 0 {
+                result.push_str(line);
+                result.push_str("&#10;");
+            }
+            continue;
+        }
+
+        result.push_str(line);
+        result.push('\n');
+        open_pre = (open_pre + line.matches("<pre").count())
+            .saturating_sub(line.matches("</pre>").count());
+    }
+
+    result.truncate(result.trim_end_matches('\n').len());
+    result
+}
+
+/// Render all shortcodes in `markdown` using the templates in [SHORTCODES_DIR].
+///
+/// Inline shortcodes look like `{{ name(key="value") }}`, block shortcodes like
+/// `{% name(key="value") %}body{% end %}`, the body is available as [BODY].
+/// Shortcodes in code (see [code_ranges]) and text that does not parse as shortcode are kept as they are,
+/// a shortcode without template is an error.
+///
+/// # Examples
+///
+/// ````
+/// use markhor::shortcodes::render_shortcodes;
+/// use tera::Tera;
+///
+/// let mut tera = Tera::default();
+/// tera.add_raw_templates(vec![
+///     ("shortcodes/youtube.html", r#"<iframe src="https://www.youtube.com/embed/{{ id }}"></iframe>"#),
+///     ("shortcodes/note.html", r#"<div class="note {{ kind }}">{{ body }}</div>"#),
+/// ]).unwrap();
+///
+/// let markdown = r#"{{ youtube(id="dQw4w9WgXcQ") }}
+///
+/// {% note(kind="warning") %}Careful!{% end %}
+///
+/// {{ not a shortcode }}
+/// ```
+/// {{ youtube(id="in code") }}
+/// ```
+/// `{{ youtube(id="inline code") }}`
+///
+///     {{ youtube(id="indented code") }}
+/// "#;
+///
+/// assert_eq!(render_shortcodes(markdown, &tera).unwrap(), r#"<iframe src="https://www.youtube.com/embed/dQw4w9WgXcQ"></iframe>
+///
+/// <div class="note warning">Careful!</div>
+///
+/// {{ not a shortcode }}
+/// ```
+/// {{ youtube(id="in code") }}
+/// ```
+/// `{{ youtube(id="inline code") }}`
+///
+///     {{ youtube(id="indented code") }}
+/// "#);
+///
+/// assert!(render_shortcodes("{{ unknown() }}", &tera).is_err());
+/// ````
+pub fn render_shortcodes(markdown: &str, tera: &Tera) -> Result<String, tera::Error> {
+    let code = code_ranges(markdown);
+    let in_code = |pos: usize| code.iter().find(|range| range.contains(&pos));
+
+    let mut html = String::with_capacity(markdown.len());
+    // everything before `copied` is already part of `html`
+    let mut copied = 0;
+    let mut pos = 0;
+
+    while let Some(start) = markdown[pos..].find('{').map(|index| pos + index) {
+        if let Some(range) = in_code(start) {
+            pos = range.end;
+            continue;
+        }
+        pos = start + 1;
+
+        let is_block = match markdown[start..].get(..2) {
+            Some("{{") => false,
+            Some("{%") => true,
+            _ => continue,
+        };
+
+        let mut parser = Parser {
+            input: markdown,
+            pos: start + 2,
+        };
+        let Some(call) = parser.call() else {
+            continue;
+        };
+        if parser.eat(if is_block { "%}" } else { "}}" }).is_none() {
+            continue;
+        }
+
+        let template = format!("{SHORTCODES_DIR}/{}.html", call.name);
+        if !has_template(tera, &template) {
+            return Err(tera::Error::msg(format!(
+                "Unknown shortcode {}, there is no template {template}",
+                call.name
+            )));
+        }
+
+        let rendered = if is_block {
+            let Some((body_end, end)) = find_end(&markdown[parser.pos..]) else {
+                return Err(tera::Error::msg(format!(
+                    "Shortcode {} is missing its {{% end %}}",
+                    call.name
+                )));
+            };
+            let body = &markdown[parser.pos..parser.pos + body_end];
+            parser.pos += end;
+            render_call(tera, &call, Some(body))?
+        } else {
+            render_call(tera, &call, None)?
+        };
+
+        html.push_str(&markdown[copied..start]);
+        html.push_str(&rendered);
+        copied = parser.pos;
+        pos = parser.pos;
+    }
+
+    html.push_str(&markdown[copied..]);
+    Ok(html)
+}
+
+/// [render_shortcodes] of a single document, with a new [MarkdownFilter] using `options` and `link_targets`
+/// for the shortcode bodies.
+///
+/// Returns the rendered markdown and the [FilterOutput], so the document can avoid the heading ids of its shortcodes
+/// and include their links.
+pub fn render_document_shortcodes(
+    markdown: &str,
+    tera: &mut Tera,
+    options: &MarkdownOptions,
+    link_targets: &Arc<LinkTargets>,
+) -> Result<(String, FilterOutput), tera::Error> {
+    let filter = MarkdownFilter::new(options.clone(), link_targets.clone());
+    tera.register_filter(filters::MARKDOWN, filter.clone());
+
+    let result = render_shortcodes(markdown, tera);
+    tera.register_filter(
+        filters::MARKDOWN,
+        MarkdownFilter::new(options.clone(), link_targets.clone()),
+    );
+
+    Ok((result?, filter.take_output()))
+}
+
+/// Message of `error` and all its sources, as tera errors only show the outermost message
+pub fn error_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message.push_str(&format!(": {error}"));
+        source = error.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{remove_blank_lines, Call, Parser};
+
+    fn parse(input: &str) -> Option<Call<'_>> {
+        Parser { input, pos: 0 }.call()
+    }
+
+    #[test]
+    fn test_remove_blank_lines() {
+        assert_eq!(
+            remove_blank_lines("<div>\n\n<pre><code>a\n\n  \nb\n</code></pre>\n\n</div>\n"),
+            "<div>\n<pre><code>a\n&#10;  &#10;b\n</code></pre>\n</div>"
+        );
+    }
+
+    #[test]
+    fn test_parse_call() {
+        let call =
+            parse(r#"figure(src="a \"b\".png", alt='it\'s', width=300, scale=1.5, lazy=true)"#);
+        let call = call.unwrap();
+
+        assert_eq!(call.name, "figure");
+        assert_eq!(
+            Value::Object(call.args),
+            json!({"src": "a \"b\".png", "alt": "it's", "width": 300, "scale": 1.5, "lazy": true})
+        );
+
+        assert_eq!(parse(" empty( ) ").unwrap().args.len(), 0);
+        assert!(parse("missing_paren").is_none());
+        assert!(parse("bad(key)").is_none());
+        assert!(parse(r#"unterminated(key="value)"#).is_none());
+        assert!(parse("1number()").is_none());
+    }
+}
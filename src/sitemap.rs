@@ -0,0 +1,135 @@
+use crate::config::Config;
+use crate::escape_xml;
+use crate::{OutputFile, PostMeta};
+
+/// file name of the sitemap
+pub const SITEMAP: &str = "sitemap.xml";
+/// file name of the generated robots.txt
+pub const ROBOTS_TXT: &str = "robots.txt";
+
+pub mod priorities {
+    pub const INDEX: f32 = 1.0;
+    pub const POST: f32 = 0.8;
+    pub const PAGE: f32 = 0.6;
+    pub const CATEGORY: f32 = 0.5;
+    pub const SERIES: f32 = 0.4;
+    pub const TAG: f32 = 0.3;
+}
+
+/// A single URL in the sitemap
+pub struct SitemapEntry {
+    /// path relative to the output directory
+    pub path: String,
+    /// last modification date (`YYYY-MM-DD`)
+    pub lastmod: Option<String>,
+    pub priority: f32,
+}
+
+impl SitemapEntry {
+    /// Entry for a post or page, modified at its `updated` or `date` header value.
+    pub fn for_post(meta: &PostMeta, priority: f32) -> Self {
+        let header = meta.header.as_ref();
+
+        SitemapEntry {
+            path: meta.rendered_to.clone(),
+            lastmod: header
+                .and_then(|h| h.updated.as_ref().or(h.date.as_ref()))
+                .cloned(),
+            priority,
+        }
+    }
+}
+
+/// Generate `sitemap.xml` and, if enabled, a `robots.txt` pointing at it.
+///
+/// Returns no files if there is no [Config::base_url], as sitemaps require absolute URLs.
+pub fn render_sitemap(config: &Config, entries: &[SitemapEntry]) -> Vec<OutputFile> {
+    if !config.sitemap.enabled || config.base_url.is_none() {
+        return Vec::new();
+    }
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+
+    for entry in entries {
+        let url = config.absolute_url(&entry.path).unwrap_or_default();
+
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", escape_xml(&url)));
+        if let Some(lastmod) = &entry.lastmod {
+            xml.push_str(&format!("    <lastmod>{}</lastmod>\n", escape_xml(lastmod)));
+        }
+        xml.push_str(&format!("    <priority>{:.1}</priority>\n", entry.priority));
+        xml.push_str("  </url>\n");
+    }
+
+    xml.push_str("</urlset>\n");
+
+    let mut files = vec![OutputFile {
+        path: SITEMAP.to_string(),
+        content: xml,
+    }];
+
+    if config.sitemap.robots_txt {
+        files.push(OutputFile {
+            path: ROBOTS_TXT.to_string(),
+            content: format!(
+                "User-agent: *\nAllow: /\nSitemap: {}\n",
+                config.absolute_url(SITEMAP).unwrap_or_default()
+            ),
+        });
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{priorities, render_sitemap, SitemapEntry};
+    use crate::config::Config;
+    use crate::{PostHeader, PostMeta};
+
+    #[test]
+    fn test_render_sitemap() {
+        let config = Config {
+            base_url: Some("https://example.com".to_string()),
+            ..Default::default()
+        };
+
+        let post = PostMeta {
+            source_file: "post.md".to_string(),
+            rendered_to: "notes/post.html".to_string(),
+            header: Some(PostHeader {
+                date: Some("2022-01-01".to_string()),
+                updated: Some("2022-03-05".to_string()),
+                ..Default::default()
+            }),
+            preview_text: String::new(),
+        };
+
+        let entries = vec![
+            SitemapEntry {
+                path: "index.html".to_string(),
+                lastmod: None,
+                priority: priorities::INDEX,
+            },
+            SitemapEntry::for_post(&post, priorities::POST),
+        ];
+
+        let files = render_sitemap(&config, &entries);
+        assert_eq!(files.len(), 2);
+
+        let sitemap = &files[0].content;
+        assert!(sitemap
+            .contains("<loc>https://example.com/index.html</loc>\n    <priority>1.0</priority>"));
+        assert!(sitemap.contains("<loc>https://example.com/notes/post.html</loc>\n    <lastmod>2022-03-05</lastmod>\n    <priority>0.8</priority>"));
+
+        assert_eq!(files[1].path, "robots.txt");
+        assert!(files[1]
+            .content
+            .contains("Sitemap: https://example.com/sitemap.xml"));
+
+        assert!(render_sitemap(&Config::default(), &entries).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::{slugify, PostMeta};
+
+/// directory containing the tag pages and the tag index, relative to the output directory
+pub const TAGS_DIR: &str = "tags";
+
+/// A tag used by at least one post, see [`crate::PostHeader::tags`]
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub slug: String,
+    /// path of the tag page, relative to the output directory
+    pub rendered_to: String,
+    /// number of posts with this tag
+    pub count: usize,
+}
+
+/// Group posts by their tags, keeping the order of `posts` within each tag
+///
+/// Fails if a tag has no usable [slugify]d name (e.g. `???`), or if two tags share the same
+/// slug (e.g. `Rust` and `rust`), as their pages would overwrite each other.
+///
+/// # Examples
+///
+/// ```
+/// use markhor::{PostHeader, PostMeta};
+/// use markhor::tags::collect_tags;
+///
+/// let post = |name: &str, tags: &[&str]| PostMeta {
+///     source_file: format!("{name}.md"),
+///     rendered_to: format!("{name}.html"),
+///     header: Some(PostHeader {
+///         tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+///         ..Default::default()
+///     }),
+///     preview_text: String::new(),
+/// };
+///
+/// let a = post("a", &["Rust", "web"]);
+/// let b = post("b", &["Rust"]);
+///
+/// let tags = collect_tags(&[&a, &b]).unwrap();
+///
+/// assert_eq!(tags.len(), 2);
+/// let (rust, rust_posts) = &tags[0];
+/// assert_eq!(rust.name, "Rust");
+/// assert_eq!(rust.rendered_to, "tags/rust.html");
+/// assert_eq!(rust.count, 2);
+/// assert_eq!(rust_posts[1].source_file, "b.md");
+///
+/// assert_eq!(tags[1].0.name, "web");
+/// assert_eq!(tags[1].0.count, 1);
+///
+/// let c = post("c", &["rust"]);
+/// assert_eq!(
+///     collect_tags(&[&a, &c]).unwrap_err(),
+///     "Tags \"Rust\" and \"rust\" would both be rendered to tags/rust.html"
+/// );
+/// assert!(collect_tags(&[&post("d", &["???"])]).is_err());
+/// ```
+pub fn collect_tags<'a>(posts: &[&'a PostMeta]) -> Result<Vec<(Tag, Vec<&'a PostMeta>)>, String> {
+    let mut posts_by_tag = BTreeMap::<&str, Vec<&PostMeta>>::new();
+
+    for post in posts {
+        let tags = post.header.as_ref().and_then(|h| h.tags.as_ref());
+        for tag in tags.into_iter().flatten() {
+            posts_by_tag.entry(tag).or_default().push(post);
+        }
+    }
+
+    let mut names_by_slug = HashMap::new();
+    let mut tags = Vec::with_capacity(posts_by_tag.len());
+
+    for (name, posts) in posts_by_tag {
+        let slug = slugify(name);
+        let rendered_to = format!("{TAGS_DIR}/{slug}.html");
+        if slug.is_empty() {
+            return Err(format!("Tag {name:?} can not be used in a file name"));
+        }
+        if let Some(other) = names_by_slug.insert(slug.clone(), name) {
+            return Err(format!(
+                "Tags {other:?} and {name:?} would both be rendered to {rendered_to}"
+            ));
+        }
+
+        let tag = Tag {
+            name: name.to_string(),
+            rendered_to,
+            slug,
+            count: posts.len(),
+        };
+        tags.push((tag, posts));
+    }
+
+    Ok(tags)
+}
@@ -1,14 +1,27 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 use log::{error, info};
 use tera::{Context, Tera, Value};
 
-use crate::{Post, TocHeading};
+use crate::archive::{ArchiveMonth, ArchiveYear};
+use crate::config::MarkdownOptions;
+use crate::links::LinkTargets;
+use crate::markdown::convert_markdown_with_ids;
+use crate::series::Series;
+use crate::tags::Tag;
+use crate::{escape_xml, Post, PostMeta, TocHeading};
 
 pub mod templates {
     pub const INDEX: &str = "index.html";
     pub const POST: &str = "post.html";
     pub const CATEGORY: &str = "category.html";
+    pub const PAGE: &str = "page.html";
+    pub const TAG: &str = "tag.html";
+    pub const TAG_INDEX: &str = "tags.html";
+    pub const ARCHIVE: &str = "archive.html";
+    pub const SERIES: &str = "series.html";
 }
 
 pub mod values {
@@ -16,6 +29,22 @@ pub mod values {
     pub const POST_CONTENT: &str = "markdown_content";
     /// [`crate::PostHeader`]
     pub const HEADER: &str = "header";
+    /// metadata of the next older post (for posts), in the order of [POSTS_META]
+    pub const PREV_POST: &str = "prev_post";
+    /// metadata of the next newer post (for posts), in the order of [POSTS_META]
+    pub const NEXT_POST: &str = "next_post";
+    /// like [PREV_POST], but only considering posts of the same category
+    pub const PREV_IN_CATEGORY: &str = "prev_in_category";
+    /// like [NEXT_POST], but only considering posts of the same category
+    pub const NEXT_IN_CATEGORY: &str = "next_in_category";
+    /// metadata of the posts most related to the current post, see [`crate::related::RelatedPosts`]
+    pub const RELATED_POSTS: &str = "related_posts";
+    /// metadata of the posts linking to the current post, see [`crate::graph::LinkGraph::backlinks`]
+    pub const BACKLINKS: &str = "backlinks";
+    /// series of the current post or series overview page, see [`crate::series::Series`]
+    pub const SERIES: &str = "series";
+    /// position of the current post in its series, starting at 1
+    pub const SERIES_POSITION: &str = "series_position";
     /// current category (for category overview pages)
     pub const CATEGORY: &str = "category";
     /// all [crate::Post]s of the currently rendered category
@@ -24,6 +53,26 @@ pub mod values {
     pub const POST_CATEGORIES: &str = "post_categories";
     /// metadata for all posts
     pub const POSTS_META: &str = "posts_meta";
+    /// all tags, see [`crate::tags::Tag`]
+    pub const POST_TAGS: &str = "post_tags";
+    /// current tag (for tag pages)
+    pub const TAG: &str = "tag";
+    /// metadata for all posts with the current tag, sorted newest first
+    pub const POSTS_WITH_TAG: &str = "posts_with_tag";
+    /// metadata for all standalone pages
+    pub const PAGES: &str = "pages";
+    /// current page of the index or category listing, see [`crate::pagination::Paginator`]
+    pub const PAGINATOR: &str = "paginator";
+    /// all years and months with posts, see [`crate::archive::ArchiveYear`]
+    pub const ARCHIVES: &str = "archives";
+    /// current year (for archive pages)
+    pub const ARCHIVE_YEAR: &str = "archive_year";
+    /// current month (for monthly archive pages), see [`crate::archive::ArchiveMonth`]
+    pub const ARCHIVE_MONTH: &str = "archive_month";
+    /// metadata for all posts of the current year or month, sorted newest first
+    pub const POSTS_IN_ARCHIVE: &str = "posts_in_archive";
+    /// site wide values from [`crate::config::Config`]
+    pub const SITE: &str = "site";
 }
 
 pub mod functions {
@@ -31,19 +80,85 @@ pub mod functions {
     pub const MAKE_TOC: &str = "make_toc";
 }
 
-pub fn init_tera(template_dir: &str) -> Tera {
+pub mod filters {
+    /// tera filter name for [`crate::templating::MarkdownFilter`]
+    pub const MARKDOWN: &str = "markdown";
+}
+
+pub fn init_tera(template_dir: &str) -> Result<Tera, tera::Error> {
     info!("Creating Tera");
     let mut tera = match Tera::new(template_dir) {
-        Ok(t) => t,
+        Ok(tera) => tera,
         Err(e) => {
             error!("Failed to create tera: {}", e);
-            std::process::exit(1);
+            return Err(e);
         }
     };
 
     tera.autoescape_on(vec![]);
+    tera.register_filter(filters::MARKDOWN, MarkdownFilter::default());
+
+    Ok(tera)
+}
+
+/// What a [MarkdownFilter] collected while converting the shortcode bodies of a document
+#[derive(Debug, Default)]
+pub struct FilterOutput {
+    /// ids of all converted headings
+    pub heading_ids: HashSet<String>,
+    /// source files of all linked posts, see [`crate::markdown::ConvertedMarkdown::links`]
+    pub links: Vec<String>,
+    /// internal links to missing posts
+    pub broken_links: Vec<String>,
+}
+
+/// Tera filter converting a markdown string to html, e.g. for the body of shortcodes
+///
+/// A new filter is registered for every document (see [`crate::shortcodes::render_document_shortcodes`]),
+/// so heading ids are unique within the document and its links are part of the document's links.
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownFilter {
+    options: MarkdownOptions,
+    link_targets: Arc<LinkTargets>,
+    output: Arc<Mutex<FilterOutput>>,
+}
+
+impl MarkdownFilter {
+    pub fn new(options: MarkdownOptions, link_targets: Arc<LinkTargets>) -> Self {
+        MarkdownFilter {
+            options,
+            link_targets,
+            output: Arc::default(),
+        }
+    }
 
-    tera
+    /// Everything collected by this filter and its clones so far
+    pub fn take_output(&self) -> FilterOutput {
+        std::mem::take(&mut *self.output.lock().expect("markdown filter poisoned"))
+    }
+}
+
+impl tera::Filter for MarkdownFilter {
+    fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let markdown = tera::try_get_value!(filters::MARKDOWN, "value", String, value);
+
+        let mut output = self.output.lock().expect("markdown filter poisoned");
+        let converted = convert_markdown_with_ids(
+            &markdown,
+            &self.options,
+            &self.link_targets,
+            &mut output.heading_ids,
+        );
+
+        for link in converted.links {
+            if !output.links.contains(&link) {
+                output.links.push(link);
+            }
+        }
+        output.broken_links.extend(converted.broken_links);
+
+        Ok(Value::String(converted.content))
+    }
 }
 
 /// Tera function that generates a table of contents from [`TocHeading`]s
@@ -58,9 +173,9 @@ impl tera::Function for TocBuilder {
         let open_list_item = &args["open_list_item"].as_str().unwrap_or("");
         let close_list_item = &args["close_list_item"].as_str().unwrap_or("");
 
-        let mut skip_first = args.get("skip_first").map_or(false, |value| {
-            value.as_bool().expect("failed to pass skip_first to bool")
-        });
+        let mut skip_first = args
+            .get("skip_first")
+            .is_some_and(|value| value.as_bool().expect("failed to pass skip_first to bool"));
 
         let mut html = String::new();
 
@@ -83,7 +198,11 @@ impl tera::Function for TocBuilder {
             }
 
             html.push_str(open_list_item);
-            html.push_str(heading.text.as_str());
+            html.push_str(&format!(
+                r##"<a href="#{}">{}</a>"##,
+                heading.id,
+                escape_xml(&heading.text)
+            ));
 
             html.push_str(close_list_item);
         }
@@ -111,10 +230,69 @@ pub fn error_make_toc_fn_unavailable(_args: &HashMap<String, Value>) -> tera::Re
     Err(tera::Error::msg(msg))
 }
 
+/// Pick the template for a post or page.
+///
+/// A `template` set in the [`crate::PostHeader`] is always used, otherwise
+/// `specific` is used if it exists, falling back to `default`.
+fn resolve_template<'a>(
+    tera: &Tera,
+    header_template: Option<&'a str>,
+    specific: Option<String>,
+    default: &'a str,
+) -> Cow<'a, str> {
+    if let Some(template) = header_template {
+        return Cow::Borrowed(template);
+    }
+
+    match specific {
+        Some(template) if has_template(tera, &template) => Cow::Owned(template),
+        _ => Cow::Borrowed(default),
+    }
+}
+
+/// Render a post using the first available template of
+/// `template` from its header, `post-<category>.html` and [`templates::POST`]
 pub fn render_post_into_template(
     tera: &mut Tera,
     context: &mut Context,
     post: &Post,
+) -> Result<String, tera::Error> {
+    let header = post.meta.header.as_ref();
+    let template = resolve_template(
+        tera,
+        header.and_then(|h| h.template.as_deref()),
+        header
+            .and_then(|h| h.category.as_ref())
+            .map(|category| format!("post-{category}.html")),
+        templates::POST,
+    );
+
+    render_markdown_into_template(tera, context, post, &template)
+}
+
+/// Render a standalone page (see [`crate::markdown::convert_pages`]) using
+/// `template` from its header or [`templates::PAGE`]
+pub fn render_page_into_template(
+    tera: &mut Tera,
+    context: &mut Context,
+    page: &Post,
+) -> Result<String, tera::Error> {
+    let header = page.meta.header.as_ref();
+    let template = resolve_template(
+        tera,
+        header.and_then(|h| h.template.as_deref()),
+        None,
+        templates::PAGE,
+    );
+
+    render_markdown_into_template(tera, context, page, &template)
+}
+
+fn render_markdown_into_template(
+    tera: &mut Tera,
+    context: &mut Context,
+    post: &Post,
+    template: &str,
 ) -> Result<String, tera::Error> {
     context.insert(values::POST_CONTENT, &post.content);
     context.insert(values::HEADER, &post.meta.header);
@@ -124,7 +302,7 @@ pub fn render_post_into_template(
     };
     tera.register_function(functions::MAKE_TOC, toc_builder);
 
-    let result = tera.render(templates::POST, context);
+    let result = tera.render(template, context);
     tera.register_function(functions::MAKE_TOC, error_make_toc_fn_unavailable);
 
     result
@@ -138,12 +316,18 @@ pub fn render_category_page(
     tera: &Tera,
     context: &mut Context,
     category: &str,
-    posts: &[Post],
+    posts: &[&Post],
 ) -> Result<String, tera::Error> {
     context.insert(values::CATEGORY, category);
     context.insert(values::POSTS_IN_CATEGORY, &posts);
 
-    let category_page = tera.render(templates::CATEGORY, context)?;
+    let template = resolve_template(
+        tera,
+        None,
+        Some(format!("category-{category}.html")),
+        templates::CATEGORY,
+    );
+    let category_page = tera.render(&template, context)?;
 
     context.remove(values::CATEGORY);
     context.remove(values::POSTS_IN_CATEGORY);
@@ -151,12 +335,73 @@ pub fn render_category_page(
     Ok(category_page)
 }
 
+pub fn render_tag_page(
+    tera: &Tera,
+    context: &mut Context,
+    tag: &Tag,
+    posts: &[&PostMeta],
+) -> Result<String, tera::Error> {
+    context.insert(values::TAG, tag);
+    context.insert(values::POSTS_WITH_TAG, posts);
+
+    let tag_page = tera.render(templates::TAG, context)?;
+
+    context.remove(values::TAG);
+    context.remove(values::POSTS_WITH_TAG);
+
+    Ok(tag_page)
+}
+
+pub fn render_series_page(
+    tera: &Tera,
+    context: &mut Context,
+    series: &Series,
+) -> Result<String, tera::Error> {
+    context.insert(values::SERIES, series);
+
+    let series_page = tera.render(templates::SERIES, context)?;
+
+    context.remove(values::SERIES);
+
+    Ok(series_page)
+}
+
+pub fn render_tag_index(tera: &Tera, context: &mut Context) -> Result<String, tera::Error> {
+    tera.render(templates::TAG_INDEX, context)
+}
+
+/// Whether `template` exists, e.g. to skip optional pages
+pub fn has_template(tera: &Tera, template: &str) -> bool {
+    tera.get_template_names().any(|name| name == template)
+}
+
+/// Render a yearly archive page, or a monthly one if `month` is given
+pub fn render_archive_page(
+    tera: &Tera,
+    context: &mut Context,
+    year: &ArchiveYear,
+    month: Option<&ArchiveMonth>,
+    posts: &[&PostMeta],
+) -> Result<String, tera::Error> {
+    context.insert(values::ARCHIVE_YEAR, &year.year);
+    context.insert(values::ARCHIVE_MONTH, &month);
+    context.insert(values::POSTS_IN_ARCHIVE, posts);
+
+    let archive_page = tera.render(templates::ARCHIVE, context)?;
+
+    context.remove(values::ARCHIVE_YEAR);
+    context.remove(values::ARCHIVE_MONTH);
+    context.remove(values::POSTS_IN_ARCHIVE);
+
+    Ok(archive_page)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::TocBuilder;
+    use super::{resolve_template, TocBuilder};
     use crate::TocHeading;
     use std::collections::HashMap;
-    use tera::{Function, Value};
+    use tera::{Function, Tera, Value};
 
     fn test_headings() -> Vec<TocHeading> {
         // 1 (h1)
@@ -168,21 +413,25 @@ mod tests {
                 level: 1,
                 prev_level: None,
                 text: "1".to_string(),
+                id: "1".to_string(),
             },
             TocHeading {
                 level: 2,
                 prev_level: Some(1),
                 text: "1.1".to_string(),
+                id: "11".to_string(),
             },
             TocHeading {
                 level: 3,
                 prev_level: Some(2),
                 text: "1.1.1".to_string(),
+                id: "111".to_string(),
             },
             TocHeading {
                 level: 2,
                 prev_level: Some(3),
                 text: "1.2".to_string(),
+                id: "12".to_string(),
             },
         ]
     }
@@ -222,21 +471,42 @@ mod tests {
 
         let html: String = tera::from_value(html).unwrap();
 
-        let mut expected = r"
+        let expected = r##"
         <ul>
-            <li>1</li>
+            <li><a href="#1">1</a></li>
             <ul>
-            <li>1.1</li>
+            <li><a href="#11">1.1</a></li>
                 <ul>
-                    <li>1.1.1</li>
+                    <li><a href="#111">1.1.1</a></li>
                 </ul>
-            <li>1.2</li>
+            <li><a href="#12">1.2</a></li>
             </ul>
-        </ul>"
-            .to_string();
+        </ul>"##;
+        assert_eq!(html, remove_indentation(expected));
+    }
 
-        remove_whitespace(&mut expected);
-        assert_eq!(html, expected);
+    #[test]
+    fn test_toc_builder_should_escape_heading_text() {
+        let toc_builder = TocBuilder {
+            headings: vec![TocHeading {
+                level: 2,
+                prev_level: None,
+                text: "Using Vec<T> & more".to_string(),
+                id: "using-vec-t-more".to_string(),
+            }],
+        };
+
+        let html = toc_builder
+            .call(&default_args())
+            .expect("failed to call toc builder");
+
+        let html: String = tera::from_value(html).unwrap();
+
+        let expected = r##"
+        <ul>
+            <li><a href="#using-vec-t-more">Using Vec&lt;T&gt; &amp; more</a></li>
+        </ul>"##;
+        assert_eq!(html, remove_indentation(expected));
     }
 
     #[test]
@@ -254,21 +524,48 @@ mod tests {
         let html: String = tera::from_value(html).unwrap();
 
         // first heading an outer ul is skipped
-        let mut expected = r"
+        let expected = r##"
             <ul>
-            <li>1.1</li>
+            <li><a href="#11">1.1</a></li>
                 <ul>
-                    <li>1.1.1</li>
+                    <li><a href="#111">1.1.1</a></li>
                 </ul>
-            <li>1.2</li>
-            </ul>"
-            .to_string();
+            <li><a href="#12">1.2</a></li>
+            </ul>"##;
+        assert_eq!(html, remove_indentation(expected));
+    }
 
-        remove_whitespace(&mut expected);
-        assert_eq!(html, expected);
+    #[test]
+    fn test_resolve_template() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("post.html", ""),
+            ("post-photos.html", ""),
+            ("landing.html", ""),
+        ])
+        .unwrap();
+
+        let resolve = |header_template, specific: Option<&str>| {
+            resolve_template(
+                &tera,
+                header_template,
+                specific.map(str::to_string),
+                "post.html",
+            )
+            .to_string()
+        };
+
+        assert_eq!(resolve(None, None), "post.html");
+        assert_eq!(resolve(None, Some("post-notes.html")), "post.html");
+        assert_eq!(resolve(None, Some("post-photos.html")), "post-photos.html");
+        assert_eq!(
+            resolve(Some("landing.html"), Some("post-photos.html")),
+            "landing.html"
+        );
     }
 
-    fn remove_whitespace(s: &mut String) {
-        s.retain(|c| !c.is_whitespace());
+    /// join the lines of `s` without their indentation
+    fn remove_indentation(s: &str) -> String {
+        s.lines().map(str::trim).collect()
     }
 }
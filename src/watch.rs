@@ -1,25 +1,28 @@
 use std::{path::Path, sync::mpsc::channel, time::Duration};
 
-use log::{error, trace};
+use log::{error, trace, warn};
 use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
 
-pub fn watch_directories<F, P>(
-    templates_dir: P,
-    posts_dir: P,
-    static_dir: P,
-    listener: F,
-) -> notify::Result<()>
+/// Watch `dirs` recursively and call `listener` on changes.
+///
+/// Directories that don't exist are skipped.
+pub fn watch_directories<F, P>(dirs: &[P], listener: F) -> notify::Result<()>
 where
     P: AsRef<Path>,
-    F: FnOnce(notify::DebouncedEvent) + Copy,
+    F: Fn(notify::DebouncedEvent),
 {
     let (tx, rx) = channel();
 
     let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(1))?;
 
-    (watcher.watch(templates_dir, RecursiveMode::Recursive))?;
-    (watcher.watch(static_dir, RecursiveMode::Recursive))?;
-    (watcher.watch(posts_dir, RecursiveMode::Recursive))?;
+    for dir in dirs {
+        let dir = dir.as_ref();
+        if dir.is_dir() {
+            (watcher.watch(dir, RecursiveMode::Recursive))?;
+        } else {
+            warn!("Not watching {}, directory does not exist", dir.display());
+        }
+    }
 
     loop {
         match rx.recv() {